@@ -0,0 +1,117 @@
+use crate::cli::Options;
+use crate::easy_task::bvh_node::{BvhConfig, BvhLayout, BvhSplit};
+use crate::easy_task::hittable::HitRecord;
+use crate::easy_task::interval::Interval;
+use crate::easy_task::rtweekend::{INFINITY, seed_thread_rng};
+use crate::easy_task::scene::Scene;
+use std::time::Instant;
+
+const TRAVERSAL_RAYS: usize = 200_000;
+const BENCH_SEED: u64 = 2025;
+
+struct Measurement {
+    build_ms: f64,
+    ns_per_ray: f64,
+    hit_ratio: f64,
+    render_s: f64,
+}
+
+// 参与比较的配置，第一项作为计算加速比的基准
+const CONFIGS: [(BvhSplit, BvhLayout); 4] = [
+    (BvhSplit::Median, BvhLayout::Tree),
+    (BvhSplit::Sah, BvhLayout::Tree),
+    (BvhSplit::Median, BvhLayout::Flat),
+    (BvhSplit::Sah, BvhLayout::Flat),
+];
+
+fn measure(
+    build: &dyn Fn(BvhConfig) -> Scene,
+    split: BvhSplit,
+    layout: BvhLayout,
+    options: &Options,
+) -> Measurement {
+    // 每种配置用同一个种子构建，保证场景里的随机物体完全相同
+    seed_thread_rng(BENCH_SEED);
+
+    let start = Instant::now();
+    let mut scene = build(BvhConfig { split, layout });
+    let build_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let rays = scene.camera.primary_rays(TRAVERSAL_RAYS);
+    let mut rec = HitRecord::default();
+    let mut hits = 0;
+    let mut best = f64::INFINITY;
+    // 取三轮中最快的一轮，减少其它进程带来的抖动
+    for _ in 0..3 {
+        hits = 0;
+        let start = Instant::now();
+        for r in &rays {
+            if scene
+                .world
+                .hit(r, &Interval::new(0.001, INFINITY), &mut rec)
+            {
+                hits += 1;
+            }
+        }
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    let ns_per_ray = best * 1e9 / rays.len() as f64;
+
+    let cam = &mut scene.camera;
+    cam.image_width = 200;
+    cam.samples_per_pixel = 16;
+    options.apply(cam);
+    cam.seed = Some(BENCH_SEED);
+    cam.output_path = std::env::temp_dir()
+        .join(format!(
+            "raytracer-bench-{}-{}.ppm",
+            split.name(),
+            layout.name()
+        ))
+        .to_string_lossy()
+        .into_owned();
+    let start = Instant::now();
+    scene.render();
+    let render_s = start.elapsed().as_secs_f64();
+
+    Measurement {
+        build_ms,
+        ns_per_ray,
+        hit_ratio: hits as f64 / rays.len() as f64,
+        render_s,
+    }
+}
+
+// 比较不同划分策略和内存布局在各个场景上的构建时间、光线求交耗时和渲染时间
+pub fn bench_bvh(scenes: &[(&str, &dyn Fn(BvhConfig) -> Scene)], options: &Options) {
+    let mut rows = Vec::new();
+    for (name, build) in scenes {
+        let results: Vec<Measurement> = CONFIGS
+            .iter()
+            .map(|&(split, layout)| measure(*build, split, layout, options))
+            .collect();
+        rows.push((name, results));
+    }
+
+    println!();
+    println!(
+        "{:<14} {:<12} {:>10} {:>10} {:>8} {:>10} {:>9} {:>9}",
+        "scene", "bvh", "build ms", "ns/ray", "hits", "render s", "ray x", "render x"
+    );
+    for (name, results) in &rows {
+        let base = &results[0];
+        for (&(split, layout), m) in CONFIGS.iter().zip(results) {
+            println!(
+                "{:<14} {:<12} {:>10.1} {:>10.1} {:>7.1}% {:>10.2} {:>8.2}x {:>8.2}x",
+                name,
+                format!("{}/{}", split.name(), layout.name()),
+                m.build_ms,
+                m.ns_per_ray,
+                m.hit_ratio * 100.0,
+                m.render_s,
+                base.ns_per_ray / m.ns_per_ray,
+                base.render_s / m.render_s
+            );
+        }
+    }
+}
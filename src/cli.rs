@@ -0,0 +1,408 @@
+use crate::easy_task::bvh_node::{BvhConfig, BvhLayout, BvhSplit};
+use crate::easy_task::camera::{Camera, MisHeuristic};
+use crate::easy_task::image_writer::{FORMATS, format_for_path, writer_for_format};
+use crate::easy_task::tone_mapping::ToneMapper;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub image_width: Option<i32>,
+    pub samples_per_pixel: Option<i32>,
+    pub max_depth: Option<i32>,
+    pub aspect_ratio: Option<f64>,
+    pub output: Option<String>,
+    pub ldr_output: Option<String>,
+    pub environment: Option<String>,
+    pub env_intensity: Option<f64>,
+    pub env_rotation: Option<f64>,
+    pub sky: bool,
+    pub sun_elevation: Option<f64>,
+    pub sun_azimuth: Option<f64>,
+    pub turbidity: Option<f64>,
+    pub exposure: Option<f64>,
+    pub tone_mapper: Option<ToneMapper>,
+    pub white_point: Option<f64>,
+    pub format: Option<String>,
+    pub threads: Option<usize>,
+    pub tile_size: Option<usize>,
+    pub seed: Option<u64>,
+    pub mis: Option<MisHeuristic>,
+    pub rr_depth: Option<Option<i32>>,
+    pub spectral: bool,
+    pub bvh: Option<BvhSplit>,
+    pub bvh_layout: Option<BvhLayout>,
+}
+
+pub enum Command {
+    Render(Options),
+    Bench(Options),
+    Help,
+}
+
+fn parse_positive<T: std::str::FromStr + PartialOrd + Default>(
+    flag: &str,
+    value: &str,
+) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(format!(
+            "{} expects a positive number, got \"{}\"",
+            flag, value
+        )),
+    }
+}
+
+fn parse_number(flag: &str, value: &str, what: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| format!("{} expects {}, got \"{}\"", flag, what, value))
+}
+
+// 宽高比既可以写成小数，也可以写成 16/9 这种分数
+fn parse_aspect_ratio(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(['/', ':']) {
+        Some((w, h)) => match (w.trim().parse::<f64>(), h.trim().parse::<f64>()) {
+            (Ok(w), Ok(h)) if h != 0.0 => w / h,
+            _ => f64::NAN,
+        },
+        None => value.parse().unwrap_or(f64::NAN),
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("invalid aspect ratio \"{}\"", value))
+    }
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut bench = false;
+
+    while let Some(arg) = args.next() {
+        // 同时支持 "--width 400" 和 "--width=400"
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(Command::Help);
+        }
+        if !flag.starts_with('-') {
+            if options.scene.is_some() {
+                return Err(format!("unexpected argument \"{}\"", arg));
+            }
+            options.scene = Some(arg);
+            continue;
+        }
+
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} requires a value", flag))
+        };
+        match flag.as_str() {
+            "--scene" => options.scene = Some(value()?),
+            "-w" | "--width" => options.image_width = Some(parse_positive(&flag, &value()?)?),
+            "-s" | "--spp" | "--samples" => {
+                options.samples_per_pixel = Some(parse_positive(&flag, &value()?)?)
+            }
+            "-d" | "--max-depth" => options.max_depth = Some(parse_positive(&flag, &value()?)?),
+            "-a" | "--aspect-ratio" => options.aspect_ratio = Some(parse_aspect_ratio(&value()?)?),
+            "-o" | "--output" => options.output = Some(value()?),
+            "--ldr" => {
+                let path = value()?;
+                let hdr = format_for_path(&path)
+                    .and_then(writer_for_format)
+                    .map(|w| w.high_dynamic_range());
+                if hdr != Some(false) {
+                    return Err(format!(
+                        "--ldr expects a .png or .ppm path, got \"{}\"",
+                        path
+                    ));
+                }
+                options.ldr_output = Some(path);
+            }
+            "-f" | "--format" => {
+                let format = value()?.to_ascii_lowercase();
+                if !FORMATS.contains(&format.as_str()) {
+                    return Err(format!(
+                        "unsupported format \"{}\" (available: {})",
+                        format,
+                        FORMATS.join(", ")
+                    ));
+                }
+                options.format = Some(format);
+            }
+            "-e" | "--exposure" => {
+                options.exposure = Some(parse_number(&flag, &value()?, "a number of stops")?)
+            }
+            "--tonemap" => {
+                let v = value()?;
+                options.tone_mapper = Some(ToneMapper::from_name(&v).ok_or_else(|| {
+                    format!(
+                        "unknown tone mapper \"{}\" (available: {})",
+                        v,
+                        ToneMapper::ALL.map(|op| op.name()).join(", ")
+                    )
+                })?);
+            }
+            "--white-point" => options.white_point = Some(parse_positive(&flag, &value()?)?),
+            "--environment" => options.environment = Some(value()?),
+            "--env-intensity" => options.env_intensity = Some(parse_positive(&flag, &value()?)?),
+            "--env-rotation" => {
+                options.env_rotation = Some(parse_number(&flag, &value()?, "an angle in degrees")?)
+            }
+            "--sky" => {
+                if inline.is_some() {
+                    return Err(String::from("--sky does not take a value"));
+                }
+                options.sky = true;
+            }
+            "--sun-elevation" => {
+                let elevation = parse_number(&flag, &value()?, "an angle in degrees")?;
+                if !(-90.0..=90.0).contains(&elevation) {
+                    return Err(format!("{} must be between -90 and 90", flag));
+                }
+                options.sun_elevation = Some(elevation);
+            }
+            "--sun-azimuth" => {
+                options.sun_azimuth = Some(parse_number(&flag, &value()?, "an angle in degrees")?)
+            }
+            "--turbidity" => {
+                let turbidity = parse_number(&flag, &value()?, "a number")?;
+                if !(2.0..=10.0).contains(&turbidity) {
+                    return Err(format!("{} must be between 2 and 10", flag));
+                }
+                options.turbidity = Some(turbidity);
+            }
+            "-t" | "--threads" => options.threads = Some(parse_positive(&flag, &value()?)?),
+            "--tile-size" => options.tile_size = Some(parse_positive(&flag, &value()?)?),
+            "--seed" => {
+                let v = value()?;
+                options.seed =
+                    Some(v.parse().map_err(|_| {
+                        format!("--seed expects an unsigned integer, got \"{}\"", v)
+                    })?);
+            }
+            "--rr-depth" => {
+                let v = value()?;
+                options.rr_depth = Some(if v == "off" {
+                    None
+                } else {
+                    Some(v.parse::<i32>().ok().filter(|&n| n >= 0).ok_or_else(|| {
+                        format!(
+                            "{} expects a non-negative integer or \"off\", got \"{}\"",
+                            flag, v
+                        )
+                    })?)
+                });
+            }
+            "--spectral" => {
+                if inline.is_some() {
+                    return Err(String::from("--spectral does not take a value"));
+                }
+                options.spectral = true;
+            }
+            "--mis" => {
+                let v = value()?;
+                options.mis = Some(MisHeuristic::from_name(&v).ok_or_else(|| {
+                    format!("--mis expects \"balance\" or \"power\", got \"{}\"", v)
+                })?);
+            }
+            "--bvh" => {
+                let v = value()?;
+                options.bvh = Some(BvhSplit::from_name(&v).ok_or_else(|| {
+                    format!("--bvh expects \"median\" or \"sah\", got \"{}\"", v)
+                })?);
+            }
+            "--bvh-layout" => {
+                let v = value()?;
+                options.bvh_layout = Some(BvhLayout::from_name(&v).ok_or_else(|| {
+                    format!("--bvh-layout expects \"tree\" or \"flat\", got \"{}\"", v)
+                })?);
+            }
+            "--bench" => {
+                if inline.is_some() {
+                    return Err(String::from("--bench does not take a value"));
+                }
+                bench = true;
+            }
+            _ => return Err(format!("unknown option \"{}\"", flag)),
+        }
+    }
+
+    if options.environment.is_none()
+        && (options.env_intensity.is_some() || options.env_rotation.is_some())
+    {
+        return Err(String::from(
+            "--env-intensity and --env-rotation require --environment",
+        ));
+    }
+    if !options.sky
+        && (options.sun_elevation.is_some()
+            || options.sun_azimuth.is_some()
+            || options.turbidity.is_some())
+    {
+        return Err(String::from(
+            "--sun-elevation, --sun-azimuth and --turbidity require --sky",
+        ));
+    }
+    if options.sky && options.environment.is_some() {
+        return Err(String::from("--sky cannot be combined with --environment"));
+    }
+
+    // 没有显式指定格式时由输出文件的扩展名决定
+    if let (Some(output), None) = (&options.output, &options.format) {
+        match format_for_path(output) {
+            Some(_) => {}
+            None => {
+                return Err(format!(
+                    "cannot infer the output format from \"{}\", use --format",
+                    output
+                ));
+            }
+        }
+    }
+
+    if bench {
+        Ok(Command::Bench(options))
+    } else {
+        Ok(Command::Render(options))
+    }
+}
+
+impl Options {
+    pub fn apply(&self, cam: &mut Camera) {
+        if let Some(width) = self.image_width {
+            cam.image_width = width;
+        }
+        if let Some(spp) = self.samples_per_pixel {
+            cam.samples_per_pixel = spp;
+        }
+        if let Some(depth) = self.max_depth {
+            cam.max_depth = depth;
+        }
+        if let Some(ratio) = self.aspect_ratio {
+            cam.aspect_ratio = ratio;
+        }
+        if let Some(threads) = self.threads {
+            cam.threads = threads;
+        }
+        if let Some(tile_size) = self.tile_size {
+            cam.tile_size = tile_size;
+        }
+        if self.seed.is_some() {
+            cam.seed = self.seed;
+        }
+        if let Some(rr_depth) = self.rr_depth {
+            cam.rr_depth = rr_depth;
+        }
+        if let Some(mis) = self.mis {
+            cam.mis = mis;
+        }
+        if self.spectral {
+            cam.spectral = true;
+        }
+        if let Some(output) = &self.output {
+            cam.output_path = output.clone();
+        }
+        if let Some(exposure) = self.exposure {
+            cam.display.exposure = exposure;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            cam.display.tone_mapper = tone_mapper;
+        }
+        if self.white_point.is_some() {
+            cam.display.white_point = self.white_point;
+        }
+        if self.ldr_output.is_some() {
+            cam.ldr_output_path = self.ldr_output.clone();
+        }
+        if let Some(format) = &self.format {
+            // 只给了格式时，把默认输出路径的扩展名换成对应格式的
+            if self.output.is_none() {
+                let extension =
+                    writer_for_format(format).map_or(format.as_str(), |w| w.extension());
+                cam.output_path = std::path::Path::new(&cam.output_path)
+                    .with_extension(extension)
+                    .to_string_lossy()
+                    .into_owned();
+            }
+            cam.output_format = Some(format.clone());
+        }
+    }
+
+    // 构建场景时使用的 BVH 配置，命令行没有指定的部分取默认值
+    pub fn bvh_config(&self) -> BvhConfig {
+        BvhConfig {
+            split: self.bvh.unwrap_or_default(),
+            layout: self.bvh_layout.unwrap_or_default(),
+        }
+    }
+}
+
+pub fn print_help(scenes: &[(&str, &str)]) {
+    println!("Usage: raytracer [OPTIONS] [SCENE]");
+    println!();
+    println!("SCENE is the name of a built-in scene or the path to a .json scene file.");
+    println!();
+    println!("Options:");
+    println!("      --scene <NAME|FILE>     Scene to render (same as the positional argument)");
+    println!("  -w, --width <N>             Image width in pixels");
+    println!("  -s, --spp <N>               Samples per pixel");
+    println!("  -d, --max-depth <N>         Maximum number of ray bounces");
+    println!("  -a, --aspect-ratio <R>      Aspect ratio, e.g. 1.5 or 16/9");
+    println!("  -o, --output <PATH>         Output image path");
+    println!(
+        "      --ldr <PATH>            Also save a display-ready .png/.ppm of the same render"
+    );
+    println!(
+        "  -f, --format <FMT>          Output format: {}",
+        FORMATS.join(", ")
+    );
+    println!("  -e, --exposure <STOPS>      Exposure adjustment for .png/.ppm output (default: 0)");
+    println!(
+        "      --tonemap <OP>          Tone mapping operator: {} (default: clamp)",
+        ToneMapper::ALL.map(|op| op.name()).join(", ")
+    );
+    println!(
+        "      --white-point <L>       Luminance mapped to white by reinhard-extended (default: image maximum)"
+    );
+    println!(
+        "      --environment <FILE>    Light the scene with an equirectangular environment map"
+    );
+    println!("      --env-intensity <X>     Environment brightness multiplier (default: 1)");
+    println!("      --env-rotation <DEG>    Environment rotation around the y axis (default: 0)");
+    println!("      --sky                   Light the scene with a physical sky and sun");
+    println!("      --sun-elevation <DEG>   Sun angle above the horizon (default: 45)");
+    println!("      --sun-azimuth <DEG>     Sun direction, from -z towards +x (default: 0)");
+    println!("      --turbidity <T>         Atmospheric haziness between 2 and 10 (default: 3)");
+    println!("  -t, --threads <N>           Number of render threads (default: all cores)");
+    println!("      --tile-size <N>         Edge length of a render tile in pixels (default: 16)");
+    println!("      --seed <N>              Random seed for reproducible renders");
+    println!(
+        "      --rr-depth <N|off>      Bounces before Russian roulette may end a path (default: 3)"
+    );
+    println!(
+        "      --mis <balance|power>   Heuristic for weighting light and material samples (default: {})",
+        MisHeuristic::default().name()
+    );
+    println!(
+        "      --spectral              Trace sampled wavelengths instead of RGB (enables dispersion)"
+    );
+    println!("      --bvh <median|sah>      BVH construction strategy (default: median)");
+    println!("      --bvh-layout <tree|flat>");
+    println!("                              BVH memory layout (default: tree)");
+    println!(
+        "      --bench                 Compare BVH strategies and layouts on SCENE (default: all built-in)"
+    );
+    println!("  -h, --help                  Print this help");
+    println!();
+    println!("Built-in scenes:");
+    for (name, description) in scenes {
+        println!("  {:<27} {}", name, description);
+    }
+}
@@ -83,8 +83,8 @@ impl Aabb {
                 if t1 > ray_t.min {
                     ray_t.min = t1;
                 }
-                if t0 < ray_t.min {
-                    ray_t.min = t0;
+                if t0 < ray_t.max {
+                    ray_t.max = t0;
                 }
             }
 
@@ -95,6 +95,22 @@ impl Aabb {
         true
     }
 
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
     pub fn longest_axis(&self) -> i32 {
         if self.x.size() > self.y.size() && self.x.size() > self.z.size() {
             0
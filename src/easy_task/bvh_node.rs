@@ -1,11 +1,80 @@
 use crate::easy_task::aabb;
 use crate::easy_task::aabb::Aabb;
+use crate::easy_task::color::Color;
+use crate::easy_task::flat_bvh::FlatBvh;
 use crate::easy_task::hittable::{HitRecord, Hittable};
 use crate::easy_task::hittable_list::HittableList;
 use crate::easy_task::interval::Interval;
 use crate::easy_task::ray::Ray;
+use crate::easy_task::vec3::{Point3, Vec3};
 use std::sync::Arc;
 
+// BVH 的划分策略：Median 按最长轴排序后从中间切开，Sah 用分桶的表面积启发式选择切分位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhSplit {
+    #[default]
+    Median,
+    Sah,
+}
+
+impl BvhSplit {
+    pub fn name(self) -> &'static str {
+        match self {
+            BvhSplit::Median => "median",
+            BvhSplit::Sah => "sah",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "median" => Some(BvhSplit::Median),
+            "sah" => Some(BvhSplit::Sah),
+            _ => None,
+        }
+    }
+}
+
+// BVH 的内存布局：Tree 是 BvhNode 组成的树，Flat 是连续数组表示的 FlatBvh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhLayout {
+    #[default]
+    Tree,
+    Flat,
+}
+
+impl BvhLayout {
+    pub fn name(self) -> &'static str {
+        match self {
+            BvhLayout::Tree => "tree",
+            BvhLayout::Flat => "flat",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tree" => Some(BvhLayout::Tree),
+            "flat" => Some(BvhLayout::Flat),
+            _ => None,
+        }
+    }
+}
+
+// 建立 BVH 时使用的划分策略和布局，由命令行或场景文件决定，随场景的构建过程一路传下去
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BvhConfig {
+    pub split: BvhSplit,
+    pub layout: BvhLayout,
+}
+
+pub fn build_bvh(list: &mut HittableList, config: BvhConfig) -> Arc<dyn Hittable + Send + Sync> {
+    match config.layout {
+        BvhLayout::Tree => Arc::new(BvhNode::new_list_with(list, config.split)),
+        BvhLayout::Flat => Arc::new(FlatBvh::new_with(list, config.split)),
+    }
+}
+
+const SAH_BINS: usize = 16;
+
 #[derive(Clone)]
 pub struct BvhNode {
     left: Arc<dyn Hittable + Sync + Send>,
@@ -16,23 +85,29 @@ pub struct BvhNode {
 impl BvhNode {
     #[allow(dead_code)]
     pub fn new_list(list: &mut HittableList) -> Self {
-        BvhNode::new(&mut list.objects.clone(), 0, list.objects.len() as i32)
+        Self::new_list_with(list, BvhSplit::default())
     }
 
-    pub fn new(objects: &mut Vec<Arc<dyn Hittable + Send + Sync>>, start: i32, end: i32) -> Self {
-        let mut bbox = aabb::EMPTY;
-        for object_index in start..end {
-            bbox = Aabb::new_aabb(&bbox, objects[object_index as usize].bounding_box());
-        }
-
-        let axis = bbox.longest_axis();
+    pub fn new_list_with(list: &mut HittableList, split: BvhSplit) -> Self {
+        BvhNode::new_with(
+            &mut list.objects.clone(),
+            0,
+            list.objects.len() as i32,
+            split,
+        )
+    }
 
-        let comparator = match axis {
-            0 => Self::box_x_compare,
-            1 => Self::box_y_compare,
-            _ => Self::box_z_compare,
-        };
+    #[allow(dead_code)]
+    pub fn new(objects: &mut Vec<Arc<dyn Hittable + Send + Sync>>, start: i32, end: i32) -> Self {
+        Self::new_with(objects, start, end, BvhSplit::default())
+    }
 
+    pub fn new_with(
+        objects: &mut Vec<Arc<dyn Hittable + Send + Sync>>,
+        start: i32,
+        end: i32,
+        split: BvhSplit,
+    ) -> Self {
         let object_span = end - start;
 
         if object_span == 1 {
@@ -51,16 +126,98 @@ impl BvhNode {
                 ),
             }
         } else {
-            objects[start as usize..end as usize].sort_by(comparator);
+            let span = &mut objects[start as usize..end as usize];
+            let (count, _) = match split {
+                BvhSplit::Sah => Self::partition_sah(span),
+                BvhSplit::Median => None,
+            }
+            .unwrap_or_else(|| Self::partition_median(span));
+            let mid = start + count as i32;
 
-            let mid = start + object_span / 2;
-            let left = Arc::new(Self::new(objects, start, mid));
-            let right = Arc::new(Self::new(objects, mid, end));
+            let left = Arc::new(Self::new_with(objects, start, mid, split));
+            let right = Arc::new(Self::new_with(objects, mid, end, split));
             let bbox = Aabb::new_aabb(left.bounding_box(), right.bounding_box());
             Self { left, right, bbox }
         }
     }
 
+    // 在三个轴上把包围盒中心分进 SAH_BINS 个桶，选出 SAH 代价最小的切分并原地划分，
+    // 返回 (左边的物体个数, 切分轴)；所有中心重合等无法切分的情况返回 None，退回中位数划分
+    pub fn partition_sah(
+        objects: &mut [Arc<dyn Hittable + Send + Sync>],
+    ) -> Option<(usize, usize)> {
+        let mut centroid_bounds = aabb::EMPTY;
+        for object in objects.iter() {
+            let c = object.bounding_box().centroid();
+            centroid_bounds = Aabb::new_aabb(&centroid_bounds, &Aabb::new_point(&c, &c));
+        }
+
+        let bin_of = |object: &Arc<dyn Hittable + Send + Sync>, axis: usize| -> usize {
+            let interval = centroid_bounds.axis_interval(axis);
+            let c = object.bounding_box().centroid()[axis];
+            let b = ((c - interval.min) / interval.size() * SAH_BINS as f64) as usize;
+            b.min(SAH_BINS - 1)
+        };
+
+        // (代价, 轴, 最后一个划到左边的桶)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.axis_interval(axis).size() <= 0.0 {
+                continue;
+            }
+
+            let mut bins = [(aabb::EMPTY, 0usize); SAH_BINS];
+            for object in objects.iter() {
+                let bin = &mut bins[bin_of(object, axis)];
+                bin.0 = Aabb::new_aabb(&bin.0, object.bounding_box());
+                bin.1 += 1;
+            }
+
+            // 从右往左累积，right_cost[i] 是桶 i.. 合在一起的 面积 × 个数
+            let mut right_cost = [0.0; SAH_BINS];
+            let (mut right_box, mut right_count) = (aabb::EMPTY, 0);
+            for i in (1..SAH_BINS).rev() {
+                right_box = Aabb::new_aabb(&right_box, &bins[i].0);
+                right_count += bins[i].1;
+                right_cost[i] = right_box.surface_area() * right_count as f64;
+            }
+
+            let (mut left_box, mut left_count) = (aabb::EMPTY, 0);
+            for i in 0..SAH_BINS - 1 {
+                left_box = Aabb::new_aabb(&left_box, &bins[i].0);
+                left_count += bins[i].1;
+                if left_count == 0 || left_count == objects.len() {
+                    continue;
+                }
+                let cost = left_box.surface_area() * left_count as f64 + right_cost[i + 1];
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, i));
+                }
+            }
+        }
+
+        let (_, axis, last_left_bin) = best?;
+        let mut mid = 0;
+        for i in 0..objects.len() {
+            if bin_of(&objects[i], axis) <= last_left_bin {
+                objects.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some((mid, axis))
+    }
+
+    // 按包围盒最长轴排序后从中间切开，返回 (左边的物体个数, 切分轴)
+    pub fn partition_median(objects: &mut [Arc<dyn Hittable + Send + Sync>]) -> (usize, usize) {
+        let mut bbox = aabb::EMPTY;
+        for object in objects.iter() {
+            bbox = Aabb::new_aabb(&bbox, object.bounding_box());
+        }
+        let axis = bbox.longest_axis() as usize;
+        objects.sort_by(|a, b| Self::box_compare(a, b, axis));
+        (objects.len() / 2, axis)
+    }
+
     fn box_compare(
         a: &Arc<dyn Hittable + Sync + Send>,
         b: &Arc<dyn Hittable + Sync + Send>,
@@ -72,37 +229,17 @@ impl BvhNode {
             .partial_cmp(&b.bounding_box().axis_interval(axis_index).min)
             .unwrap()
     }
-
-    fn box_x_compare(
-        a: &Arc<dyn Hittable + Sync + Send>,
-        b: &Arc<dyn Hittable + Sync + Send>,
-    ) -> std::cmp::Ordering {
-        Self::box_compare(a, b, 0)
-    }
-
-    fn box_y_compare(
-        a: &Arc<dyn Hittable + Sync + Send>,
-        b: &Arc<dyn Hittable + Sync + Send>,
-    ) -> std::cmp::Ordering {
-        Self::box_compare(a, b, 1)
-    }
-
-    fn box_z_compare(
-        a: &Arc<dyn Hittable + Sync + Send>,
-        b: &Arc<dyn Hittable + Sync + Send>,
-    ) -> std::cmp::Ordering {
-        Self::box_compare(a, b, 2)
-    }
 }
 
 impl Hittable for BvhNode {
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        let mut ray_t = *ray_t;
-        if !self.bbox.hit(r, &mut ray_t) {
+        // 包围盒测试会收窄区间，只能作用在副本上：盒面和物体表面重合时，
+        // 收窄后的区间会因为舍入误差把真正的交点排除在外
+        if !self.bbox.hit(r, &mut ray_t.clone()) {
             return false;
         }
 
-        let hit_left = self.left.hit(r, &ray_t, rec);
+        let hit_left = self.left.hit(r, ray_t, rec);
         let hit_right = self.right.hit(
             r,
             &Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max }),
@@ -112,7 +249,92 @@ impl Hittable for BvhNode {
         hit_left || hit_right
     }
 
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, &mut ray_t.clone()) {
+            return false;
+        }
+
+        let hit_left = self.left.shadow_hit(r, ray_t, rec);
+        let hit_right = self.right.shadow_hit(
+            r,
+            &Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max }),
+            rec,
+        );
+
+        hit_left || hit_right
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        if !self.bbox.hit(r, &mut ray_t.clone()) {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        // 只有一个物体的节点左右子树是同一个物体，只能算一次
+        let left = self.left.transmittance(r, ray_t);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        left * self.right.transmittance(r, ray_t)
+    }
+
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
+
+    // 子树中各个物体的密度之和。物体的密度已经按整体归一化时（三角网格的每个面按网格的总面积）
+    // 这就是整个子树作为一个光源的密度
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        if !self.bbox.hit(&r, &mut Interval::new(0.0001, f64::INFINITY)) {
+            return 0.0;
+        }
+        let left = self.left.pdf_value(origin, direction);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        left + self.right.pdf_value(origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::constant_medium::ConstantMedium;
+    use crate::easy_task::hittable::Sphere;
+    use crate::easy_task::material::Lambertian;
+
+    // 单个物体的节点和多个物体的节点都不能改变介质的透过率
+    #[test]
+    fn medium_in_bvh_has_the_same_transmittance() {
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let medium: Arc<dyn Hittable + Send + Sync> = Arc::new(ConstantMedium::new_with_color(
+            boundary,
+            0.7,
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        let far_away = Arc::new(Sphere::new(
+            Point3::new(10.0, 10.0, 10.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = medium.transmittance(&r, &ray_t);
+        assert!((expected.x() - (-0.7f64 * 2.0).exp()).abs() < 1e-9);
+
+        let mut single = HittableList::default();
+        single.add(Arc::clone(&medium));
+        let mut pair = HittableList::default();
+        pair.add(Arc::clone(&medium));
+        pair.add(far_away);
+        for list in [&mut single, &mut pair] {
+            let bvh = BvhNode::new_list(list);
+            let tr = bvh.transmittance(&r, &ray_t);
+            assert!((tr - expected).length() < 1e-12);
+        }
+    }
 }
@@ -1,17 +1,127 @@
-use crate::easy_task::color::{Color, linear_to_gamma};
+use crate::easy_task::color::Color;
+use crate::easy_task::environment::Environment;
+use crate::easy_task::framebuffer::Framebuffer;
 use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::image_writer::save_image;
 use crate::easy_task::interval::Interval;
-use crate::easy_task::material::ScatterRecord;
-use crate::easy_task::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::easy_task::light::DeltaLight;
+use crate::easy_task::material::{Material, ScatterRecord};
 use crate::easy_task::ray::Ray;
-use crate::easy_task::rtweekend::{INFINITY, degrees_to_radians, random_double};
-use crate::easy_task::vec3::{Point3, Vec3, cross, random_in_unit_disk, unit_vector};
-use crossbeam::channel;
-use std::fs::{File, create_dir_all};
-use std::io::Write;
+use crate::easy_task::rtweekend::{
+    INFINITY, degrees_to_radians, mix_seed, random_double, seed_thread_rng,
+};
+use crate::easy_task::scheduler::render_tiles;
+use crate::easy_task::spectral::{self, SampledWavelengths};
+use crate::easy_task::tone_mapping::DisplayTransform;
+use crate::easy_task::vec3::{Point3, Vec3, cross, dot, random_in_unit_disk, unit_vector};
 use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+// 光谱模式下把 RGB 的发光值换算成当前路径各个波长上的辐射度，RGB 模式下原样返回
+fn radiance(rgb: Color) -> Color {
+    match spectral::current() {
+        Some(wavelengths) => wavelengths.illuminant(rgb),
+        None => rgb,
+    }
+}
+
+// 材质给出的 RGB 反射率同样换算到各个波长上
+fn scattering_weight(rgb: Color) -> Color {
+    spectral::reflectance(rgb)
+}
+
+// 材质的 attenuation 或 eval 换算到各个波长上，记录里标明已经是光谱值时原样使用
+fn bsdf_weight(srec: &ScatterRecord, value: Color) -> Color {
+    if srec.sampled {
+        value
+    } else {
+        scattering_weight(value)
+    }
+}
+
+// 在吸收系数为 sigma 的介质里走过 distance 后剩下的比例；没有介质时为 1
+fn beer_lambert(sigma: Option<Color>, distance: f64) -> Color {
+    let Some(sigma) = sigma else {
+        return Color::new(1.0, 1.0, 1.0);
+    };
+    let channel = |s: f64| if s > 0.0 { (-s * distance).exp() } else { 1.0 };
+    scattering_weight(Color::new(
+        channel(sigma.x()),
+        channel(sigma.y()),
+        channel(sigma.z()),
+    ))
+}
+
+// 离开表面的方向穿过了有吸收的材质时返回它的吸收系数
+fn crossed_absorption(rec: &HitRecord, direction: Vec3) -> Option<Color> {
+    let sigma = rec.mat.as_ref()?.absorption()?;
+    (dot(direction, rec.normal) < 0.0).then_some(sigma)
+}
+
+// media 是光线所在的吸收介质，最里层的在最后。沿 direction 离开表面后，
+// 穿进物体时压入它的吸收系数，穿出时弹出
+fn cross_surface(media: &mut Vec<Color>, rec: &HitRecord, direction: Vec3) {
+    if let Some(sigma) = crossed_absorption(rec, direction) {
+        if rec.front_face {
+            media.push(sigma);
+        } else {
+            media.pop();
+        }
+    }
+}
+
+// 沿 direction 离开表面后所在介质的吸收系数，用于阴影光线
+fn medium_after(media: &[Color], rec: &HitRecord, direction: Vec3) -> Option<Color> {
+    match crossed_absorption(rec, direction) {
+        Some(sigma) if rec.front_face => Some(sigma),
+        Some(_) => media.len().checked_sub(2).map(|i| media[i]),
+        None => media.last().copied(),
+    }
+}
+
+// 色散的顶点之后只有主波长沿路径继续，返回这个顶点之后的贡献要乘的系数
+fn dispersion_weight(srec: &ScatterRecord) -> Color {
+    if srec.dispersive {
+        spectral::terminate_secondary()
+    } else {
+        Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+// 多重重要性采样中组合光源采样和材质采样的权重函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MisHeuristic {
+    Balance,
+    #[default]
+    Power,
+}
+
+impl MisHeuristic {
+    pub fn name(self) -> &'static str {
+        match self {
+            MisHeuristic::Balance => "balance",
+            MisHeuristic::Power => "power",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "balance" => Some(MisHeuristic::Balance),
+            "power" => Some(MisHeuristic::Power),
+            _ => None,
+        }
+    }
+
+    // 用密度为 pdf 的策略采到某个方向时的权重，other_pdf 是另一种策略采到同一方向的密度
+    fn weight(self, pdf: f64, other_pdf: f64) -> f64 {
+        let (a, b) = match self {
+            MisHeuristic::Balance => (pdf, other_pdf),
+            MisHeuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if a + b > 0.0 { a / (a + b) } else { 0.0 }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: i32,
@@ -24,6 +134,18 @@ pub struct Camera {
     pub defocus_angle: f64, // 通过每个像素的光线的变化角度
     pub focus_dist: f64,    // 从相机观察点到完美对焦平面的距离
     pub background: Color,
+    pub environment: Option<Arc<dyn Environment + Send + Sync>>, // 设置后代替 background
+    pub delta_lights: Vec<Arc<dyn DeltaLight + Send + Sync>>,    // 点光源等无法被光线击中的光源
+    pub output_path: String,
+    pub output_format: Option<String>, // 为 None 时由输出路径的扩展名决定
+    pub ldr_output_path: Option<String>, // 同一次渲染额外保存的低动态范围图像
+    pub display: DisplayTransform,     // 保存低动态范围图像时的曝光和色调映射
+    pub threads: usize,                // 渲染线程数，0 表示使用全部 CPU 核心
+    pub tile_size: usize,              // 分块边长（像素）
+    pub seed: Option<u64>,             // 设置后每个分块使用由它派生的固定种子，结果可复现
+    pub mis: MisHeuristic,             // 组合光源采样和材质采样的权重函数
+    pub rr_depth: Option<i32>,         // 从第几次反弹开始做俄罗斯轮盘赌，None 表示不做
+    pub spectral: bool, // 每条路径追踪采样的几个波长，支持色散，最后在胶片上换算成 sRGB
 
     image_height: i32,
     sqrt_spp: i32,
@@ -55,6 +177,18 @@ impl Default for Camera {
             defocus_angle: 0.0,
             focus_dist: 10.0,
             background: Color::default(),
+            environment: None,
+            delta_lights: Vec::new(),
+            output_path: String::from("output/advanced/image1.png"),
+            output_format: None,
+            ldr_output_path: None,
+            display: DisplayTransform::default(),
+            threads: 0,
+            tile_size: 16,
+            seed: None,
+            mis: MisHeuristic::default(),
+            rr_depth: Some(3),
+            spectral: false,
 
             image_height: 0,
             sqrt_spp: 0,
@@ -74,50 +208,218 @@ impl Default for Camera {
 }
 
 impl Camera {
+    // 光线没有击中任何物体时看到的辐射度
+    fn miss_radiance(&self, direction: Vec3) -> Color {
+        radiance(match &self.environment {
+            Some(environment) => environment.radiance(direction),
+            None => self.background,
+        })
+    }
+
+    fn emitted(mat: &Arc<dyn Material + Send + Sync>, r: &Ray, rec: &HitRecord) -> Color {
+        radiance(mat.emitted(r, rec, rec.u, rec.v, rec.p))
+    }
+
+    // 俄罗斯轮盘赌：按路径通量的最大分量决定是否继续追踪，返回继续的概率，None 表示终止。
+    // 幸存的路径除以这个概率补偿被终止的路径，估计保持无偏
+    fn survival_probability(&self, depth: i32, throughput: Color) -> Option<f64> {
+        let bounces = self.max_depth - depth;
+        match self.rr_depth {
+            Some(rr_depth) if bounces >= rr_depth => {
+                let p = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.0);
+                if p > 0.0 && random_double() < p {
+                    Some(p)
+                } else {
+                    None
+                }
+            }
+            _ => Some(1.0),
+        }
+    }
+
+    // 每次反弹先对光源采样一次（下一事件估计），再按材质的分布采样出射方向继续追踪，
+    // 两种策略得到的光照用 MIS 权重组合。
+    // lights 为 None 表示场景中没有需要重要性采样的光源。
+    // throughput 是相机到当前光线起点的路径通量；bsdf_pdf 是上一个顶点按材质采样出当前光线的密度，
+    // 为 None 时（相机光线、镜面反射）光线击中的自发光不做加权
     fn ray_color(
         &self,
         r: &Ray,
-        depth: i32,
         world: &Arc<dyn Hittable + Send + Sync>,
-        lights: &Arc<dyn Hittable + Send + Sync>,
+        lights: Option<&Arc<dyn Hittable + Send + Sync>>,
     ) -> Color {
-        if depth <= 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-        let mut rec = HitRecord::default();
-        if !world.hit(r, &Interval::new(0.001, INFINITY), &mut rec) {
-            return self.background;
-        }
+        let mut color = Color::default();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut media: Vec<Color> = Vec::new();
+
+        // depth 是包括当前光线在内还剩下的光线段数
+        for depth in (1..=self.max_depth).rev() {
+            // 上一个顶点的光源采样也可能采到这个方向，自发光要乘以对应的权重
+            let emission_weight = match (bsdf_pdf, lights) {
+                (Some(pdf), Some(lights)) => {
+                    let light_pdf = lights.pdf_value(ray.origin(), ray.direction());
+                    self.mis.weight(pdf, light_pdf)
+                }
+                _ => 1.0,
+            };
+
+            let mut rec = HitRecord::default();
+            if !world.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec) {
+                color += throughput * emission_weight * self.miss_radiance(ray.direction());
+                break;
+            }
 
-        if let Some(mat) = rec.mat.clone() {
+            let distance = rec.t * ray.direction().length();
+            throughput = throughput * beer_lambert(media.last().copied(), distance);
+
+            let Some(mat) = rec.mat.clone() else {
+                break;
+            };
             let mut srec = ScatterRecord::default();
-            let color_from_emission = mat.emitted(r, &rec, rec.u, rec.v, rec.p);
+            color += throughput * emission_weight * Self::emitted(&mat, &ray, &rec);
 
-            if !mat.scatter(r, &rec, &mut srec) {
-                return color_from_emission;
+            if !mat.scatter(&ray, &rec, &mut srec) {
+                break;
             }
+            throughput = throughput * dispersion_weight(&srec);
 
             if srec.skip_pdf {
-                return srec.attenuation
-                    * self.ray_color(&srec.skip_pdf_ray, depth - 1, world, lights);
+                throughput = throughput * bsdf_weight(&srec, srec.attenuation);
+                let Some(p) = self.survival_probability(depth, throughput) else {
+                    break;
+                };
+                throughput /= p;
+                ray = srec.skip_pdf_ray;
+                cross_surface(&mut media, &rec, ray.direction());
+                bsdf_pdf = None;
+                continue;
             }
 
-            let light_ptr = Box::new(HittablePdf::new(Arc::clone(lights), rec.p));
-            let mixed_pdf = MixturePdf::new(light_ptr, srec.pdf);
+            // 连接光源得到的路径比当前顶点多一段，最后一次反弹不再连接，与按材质采样的路径长度一致
+            if depth > 1 {
+                color += throughput * self.delta_lighting(&ray, &rec, &srec, world, &media);
+                if let Some(lights) = lights {
+                    color +=
+                        throughput * self.sample_lights(&ray, &rec, &srec, world, lights, &media);
+                }
+            }
 
-            let scattered = Ray::new_time(rec.p, mixed_pdf.generate(), r.time());
-            let pdf_value = mixed_pdf.value(scattered.direction());
+            let direction = srec.pdf.generate();
+            if direction.near_zero() {
+                break;
+            }
+            let scattered = Ray::new_time(rec.p, direction, ray.time());
+            let pdf_value = srec.pdf.value(scattered.direction());
+            if pdf_value <= 0.0 {
+                break;
+            }
+            throughput = throughput * bsdf_weight(&srec, mat.eval(&ray, &rec, &srec, &scattered))
+                / pdf_value;
+            let Some(p) = self.survival_probability(depth, throughput) else {
+                break;
+            };
+            throughput /= p;
+            ray = scattered;
+            cross_surface(&mut media, &rec, ray.direction());
+            bsdf_pdf = Some(pdf_value);
+        }
 
-            let scattering_pdf = mat.scattering_pdf(r, &rec, &scattered);
+        color
+    }
 
-            let sample_color = self.ray_color(&scattered, depth - 1, world, lights);
-            let color_from_scatter = (srec.attenuation * scattering_pdf * sample_color) / pdf_value;
+    // 向 lights 采样一个方向并发出阴影光线，取它击中的第一个表面（或背景）的自发光，
+    // 这样沿途的遮挡物和其他光源都被自然地考虑进去；途经的参与介质按透过率衰减。
+    // media 是到达这个顶点的光线所在的吸收介质
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        srec: &ScatterRecord,
+        world: &Arc<dyn Hittable + Send + Sync>,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+        media: &[Color],
+    ) -> Color {
+        let Some(mat) = &rec.mat else {
+            return Color::default();
+        };
+        let shadow_ray = Ray::new_time(rec.p, lights.random(rec.p), r.time());
+        let light_pdf = lights.pdf_value(rec.p, shadow_ray.direction());
+        if light_pdf <= 0.0 {
+            return Color::default();
+        }
+        let f = bsdf_weight(srec, mat.eval(r, rec, srec, &shadow_ray));
+        if f.length_squared() <= 0.0 {
+            return Color::default();
+        }
 
-            color_from_emission + color_from_scatter
-        } else {
-            Color::default()
+        let mut light_rec = HitRecord::default();
+        let mut end = INFINITY;
+        let emitted =
+            if world.shadow_hit(&shadow_ray, &Interval::new(0.001, INFINITY), &mut light_rec) {
+                end = light_rec.t;
+                match &light_rec.mat {
+                    Some(light_mat) => Self::emitted(light_mat, &shadow_ray, &light_rec),
+                    None => Color::default(),
+                }
+            } else {
+                self.miss_radiance(shadow_ray.direction())
+            };
+
+        let weight = self
+            .mis
+            .weight(light_pdf, srec.pdf.value(shadow_ray.direction()));
+        let tr = world.transmittance(&shadow_ray, &Interval::new(0.001, end))
+            * beer_lambert(
+                medium_after(media, rec, shadow_ray.direction()),
+                end * shadow_ray.direction().length(),
+            );
+        weight * f * emitted * tr / light_pdf
+    }
+
+    // δ 光源只能显式连接：对每个光源发一条阴影光线，没有被遮挡就累加它的贡献。
+    // 贡献是材质的 f·cosθ 乘以光源的入射辐射
+    fn delta_lighting(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        srec: &ScatterRecord,
+        world: &Arc<dyn Hittable + Send + Sync>,
+        media: &[Color],
+    ) -> Color {
+        let Some(mat) = &rec.mat else {
+            return Color::default();
+        };
+        let mut total = Color::default();
+        for light in &self.delta_lights {
+            let Some(sample) = light.sample_li(rec.p) else {
+                continue;
+            };
+            let shadow_ray = Ray::new_time(rec.p, sample.direction, r.time());
+            let f = bsdf_weight(srec, mat.eval(r, rec, srec, &shadow_ray));
+            if f.length_squared() <= 0.0 {
+                continue;
+            }
+            let mut shadow_rec = HitRecord::default();
+            let ray_t = Interval::new(0.001, sample.distance * (1.0 - 1e-6));
+            if world.shadow_hit(&shadow_ray, &ray_t, &mut shadow_rec) {
+                continue;
+            }
+            let absorbed = beer_lambert(
+                medium_after(media, rec, shadow_ray.direction()),
+                sample.distance * shadow_ray.direction().length(),
+            );
+            total +=
+                f * radiance(sample.radiance) * world.transmittance(&shadow_ray, &ray_t) * absorbed;
         }
+        total
     }
+
     fn initialize(&mut self) {
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
         self.image_height = if self.image_height < 1 {
@@ -162,90 +464,74 @@ impl Camera {
     pub fn render(
         &mut self,
         world: Arc<dyn Hittable + Send + Sync>,
-        lights: Arc<dyn Hittable + Send + Sync>,
+        lights: Option<Arc<dyn Hittable + Send + Sync>>,
     ) {
+        let framebuffer = self.render_framebuffer(world, lights);
+
+        let outputs = std::iter::once((self.output_path.as_str(), self.output_format.as_deref()))
+            .chain(self.ldr_output_path.as_deref().map(|path| (path, None)));
+        for (path, format) in outputs {
+            match save_image(path, format, &framebuffer, &self.display) {
+                Ok(()) => println!("\nImage saved as \"{}\"", path),
+                Err(e) => eprintln!("\nerror: cannot save \"{}\": {}", path, e),
+            }
+        }
+    }
+
+    // 只渲染不保存，返回线性辐射度的帧缓冲
+    pub fn render_framebuffer(
+        &mut self,
+        world: Arc<dyn Hittable + Send + Sync>,
+        lights: Option<Arc<dyn Hittable + Send + Sync>>,
+    ) -> Framebuffer {
         self.initialize();
 
-        let path = "output/advanced/image1.ppm";
-        let dir_path = std::path::Path::new("output/advanced");
-        if !dir_path.exists() {
-            create_dir_all(dir_path).expect("Failed to create directory");
-        }
-        let mut file = File::create(path).expect("Failed to create file");
-
-        // 写入 PPM 文件头
-        writeln!(file, "P3").unwrap();
-        writeln!(file, "{} {}", self.image_width, self.image_height).unwrap();
-        writeln!(file, "255").unwrap();
-
-        let (tx, rx) = channel::unbounded();
-        let world = Arc::clone(&world);
-        let lights = Arc::clone(&lights);
-
-        let image_width = self.image_width;
-        let pixel_samples_scale = self.pixel_samples_scale;
-        let camera = *self;
-        let max_depth = self.max_depth;
-        let sqrt_spp = self.sqrt_spp;
-
-        crossbeam::scope(|scope| {
-            for j in 0..self.image_height {
-                let tx = tx.clone();
-                let world = Arc::clone(&world);
-                let lights = Arc::clone(&lights);
-                scope.spawn(move |_| {
-                    let mut row = String::new();
-                    for i in 0..image_width {
+        let camera = &*self;
+        render_tiles(
+            self.image_width as usize,
+            self.image_height as usize,
+            self.tile_size,
+            self.threads,
+            |tile| {
+                // 种子只和分块编号有关，与线程数和调度顺序无关
+                if let Some(seed) = camera.seed {
+                    seed_thread_rng(mix_seed(seed, tile.index as u64));
+                }
+                let mut pixels = Vec::with_capacity(tile.width() * tile.height());
+                for j in tile.y0..tile.y1 {
+                    for i in tile.x0..tile.x1 {
                         let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                        for s_j in 0..sqrt_spp {
-                            for s_i in 0..sqrt_spp {
-                                let r = camera.get_ray(i, j, s_i, s_j);
-                                pixel_color += camera.ray_color(&r, max_depth, &world, &lights);
+                        for s_j in 0..camera.sqrt_spp {
+                            for s_i in 0..camera.sqrt_spp {
+                                let r = camera.get_ray(i as i32, j as i32, s_i, s_j);
+                                let wavelengths = camera.spectral.then(SampledWavelengths::sample);
+                                spectral::set_current(wavelengths);
+                                let sample_color = camera.ray_color(&r, &world, lights.as_ref());
+                                pixel_color += match wavelengths {
+                                    Some(wavelengths) => wavelengths.to_rgb(sample_color),
+                                    None => sample_color,
+                                };
                             }
                         }
-
-                        pixel_color *= pixel_samples_scale; // pixel_samples_scale = 1.0 / samples_per_pixel
-                        let mut r = pixel_color.x();
-                        let mut g = pixel_color.y();
-                        let mut b = pixel_color.z();
-
-                        if r.is_nan() {
-                            r = 0.0;
-                        }
-                        if g.is_nan() {
-                            g = 0.0;
-                        }
-                        if b.is_nan() {
-                            b = 0.0;
-                        }
-
-                        r = linear_to_gamma(r);
-                        g = linear_to_gamma(g);
-                        b = linear_to_gamma(b);
-
-                        let intensity = Interval::new(0.0, 0.999);
-
-                        let rbyte = (256.0 * intensity.clamp(r)) as i32;
-                        let gbyte = (256.0 * intensity.clamp(g)) as i32;
-                        let bbyte = (256.0 * intensity.clamp(b)) as i32;
-                        row += &format!("{} {} {} ", rbyte, gbyte, bbyte);
+                        pixels.push(pixel_color * camera.pixel_samples_scale);
                     }
-                    tx.send((j, row)).expect("send failed");
-                });
-            }
-            drop(tx);
-        })
-        .unwrap();
-
-        let mut rows: Vec<(i32, String)> = rx.iter().collect();
-        rows.sort_by_key(|k| k.0); // 按照行数排序
-
-        for (_j, line) in rows {
-            file.write_all(line.as_bytes()).unwrap(); //  写入每一行像素数据
-            writeln!(file).unwrap(); // 在每行的像素数据之后插入换行符
-        }
+                }
+                spectral::set_current(None);
+                pixels
+            },
+        )
+    }
 
-        println!("\nImage saved as \"{}\"", path);
+    // 随机像素上的相机光线，用于基准测试加速结构
+    pub fn primary_rays(&mut self, count: usize) -> Vec<Ray> {
+        self.initialize();
+        (0..count)
+            .map(|_| {
+                let i = (random_double() * self.image_width as f64) as i32;
+                let j = (random_double() * self.image_height as f64) as i32;
+                self.get_ray(i, j, 0, 0)
+            })
+            .collect()
     }
 
     fn get_ray(&self, i: i32, j: i32, s_i: i32, s_j: i32) -> Ray {
@@ -284,3 +570,388 @@ impl Camera {
         self.center + p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::hittable::Sphere;
+    use crate::easy_task::hittable_list::HittableList;
+    use crate::easy_task::material::{Dielectric, DiffuseLight, Lambertian};
+    use crate::easy_task::quad::Quad;
+    use crate::easy_task::rough_dielectric::RoughDielectric;
+
+    type HittableRef = Arc<dyn Hittable + Send + Sync>;
+
+    // 循环版 ray_color 改写前的递归实现，原样冻结在这里作为对照
+    fn reference_ray_color(
+        cam: &Camera,
+        r: &Ray,
+        depth: i32,
+        world: &Arc<dyn Hittable + Send + Sync>,
+        lights: Option<&Arc<dyn Hittable + Send + Sync>>,
+        bsdf_pdf: Option<f64>,
+        throughput: Color,
+    ) -> Color {
+        if depth <= 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // 上一个顶点的光源采样也可能采到这个方向，自发光要乘以对应的权重
+        let emission_weight = match (bsdf_pdf, lights) {
+            (Some(pdf), Some(lights)) => {
+                let light_pdf = lights.pdf_value(r.origin(), r.direction());
+                cam.mis.weight(pdf, light_pdf)
+            }
+            _ => 1.0,
+        };
+
+        let mut rec = HitRecord::default();
+        if !world.hit(r, &Interval::new(0.001, INFINITY), &mut rec) {
+            return emission_weight * cam.miss_radiance(r.direction());
+        }
+
+        let Some(mat) = rec.mat.clone() else {
+            return Color::default();
+        };
+        let mut srec = ScatterRecord::default();
+        let color_from_emission = emission_weight * Camera::emitted(&mat, r, &rec);
+
+        if !mat.scatter(r, &rec, &mut srec) {
+            return color_from_emission;
+        }
+        let dispersion = dispersion_weight(&srec);
+        let throughput = throughput * dispersion;
+
+        if srec.skip_pdf {
+            let attenuation = scattering_weight(srec.attenuation);
+            let throughput = throughput * attenuation;
+            let Some(p) = cam.survival_probability(depth, throughput) else {
+                return color_from_emission;
+            };
+            let sample_color = reference_ray_color(
+                cam,
+                &srec.skip_pdf_ray,
+                depth - 1,
+                world,
+                lights,
+                None,
+                throughput / p,
+            );
+            return color_from_emission + dispersion * attenuation * sample_color / p;
+        }
+
+        // 连接光源得到的路径比当前顶点多一段，最后一次反弹不再连接，与按材质采样的路径长度一致
+        let mut color_from_lights = Color::default();
+        if depth > 1 {
+            color_from_lights += reference_delta_lighting(cam, r, &rec, &srec, world);
+            if let Some(lights) = lights {
+                color_from_lights += reference_sample_lights(cam, r, &rec, &srec, world, lights);
+            }
+        }
+        let color_from_lights = dispersion * color_from_lights;
+
+        let direction = srec.pdf.generate();
+        if direction.near_zero() {
+            return color_from_emission + color_from_lights;
+        }
+        let scattered = Ray::new_time(rec.p, direction, r.time());
+        let pdf_value = srec.pdf.value(scattered.direction());
+        if pdf_value <= 0.0 {
+            return color_from_emission + color_from_lights;
+        }
+        let weight = scattering_weight(mat.eval(r, &rec, &srec, &scattered)) / pdf_value;
+
+        let throughput = throughput * weight;
+        let Some(p) = cam.survival_probability(depth, throughput) else {
+            return color_from_emission + color_from_lights;
+        };
+        let sample_color = reference_ray_color(
+            cam,
+            &scattered,
+            depth - 1,
+            world,
+            lights,
+            Some(pdf_value),
+            throughput / p,
+        );
+        let color_from_scatter = dispersion * weight * sample_color / p;
+
+        color_from_emission + color_from_lights + color_from_scatter
+    }
+
+    // 同样冻结的光源采样，与 reference_ray_color 一起保持改写前的样子
+    fn reference_sample_lights(
+        cam: &Camera,
+        r: &Ray,
+        rec: &HitRecord,
+        srec: &ScatterRecord,
+        world: &Arc<dyn Hittable + Send + Sync>,
+        lights: &Arc<dyn Hittable + Send + Sync>,
+    ) -> Color {
+        let Some(mat) = &rec.mat else {
+            return Color::default();
+        };
+        let shadow_ray = Ray::new_time(rec.p, lights.random(rec.p), r.time());
+        let light_pdf = lights.pdf_value(rec.p, shadow_ray.direction());
+        if light_pdf <= 0.0 {
+            return Color::default();
+        }
+        let f = scattering_weight(mat.eval(r, rec, srec, &shadow_ray));
+        if f.length_squared() <= 0.0 {
+            return Color::default();
+        }
+
+        let mut light_rec = HitRecord::default();
+        let mut end = INFINITY;
+        let emitted =
+            if world.shadow_hit(&shadow_ray, &Interval::new(0.001, INFINITY), &mut light_rec) {
+                end = light_rec.t;
+                match &light_rec.mat {
+                    Some(light_mat) => Camera::emitted(light_mat, &shadow_ray, &light_rec),
+                    None => Color::default(),
+                }
+            } else {
+                cam.miss_radiance(shadow_ray.direction())
+            };
+
+        let weight = cam
+            .mis
+            .weight(light_pdf, srec.pdf.value(shadow_ray.direction()));
+        let tr = world.transmittance(&shadow_ray, &Interval::new(0.001, end));
+        weight * f * emitted * tr / light_pdf
+    }
+
+    fn reference_delta_lighting(
+        cam: &Camera,
+        r: &Ray,
+        rec: &HitRecord,
+        srec: &ScatterRecord,
+        world: &Arc<dyn Hittable + Send + Sync>,
+    ) -> Color {
+        let Some(mat) = &rec.mat else {
+            return Color::default();
+        };
+        let mut total = Color::default();
+        for light in &cam.delta_lights {
+            let Some(sample) = light.sample_li(rec.p) else {
+                continue;
+            };
+            let shadow_ray = Ray::new_time(rec.p, sample.direction, r.time());
+            let f = scattering_weight(mat.eval(r, rec, srec, &shadow_ray));
+            if f.length_squared() <= 0.0 {
+                continue;
+            }
+            let mut shadow_rec = HitRecord::default();
+            let ray_t = Interval::new(0.001, sample.distance * (1.0 - 1e-6));
+            if world.shadow_hit(&shadow_ray, &ray_t, &mut shadow_rec) {
+                continue;
+            }
+            total += f * radiance(sample.radiance) * world.transmittance(&shadow_ray, &ray_t);
+        }
+        total
+    }
+
+    // 缩小的 Cornell box：漫反射墙面、面光源和一个玻璃球，覆盖光源采样、MIS、镜面路径和俄罗斯轮盘赌
+    fn scene() -> (Camera, HittableRef, HittableRef) {
+        let lambertian = |r, g, b| -> Arc<dyn Material + Send + Sync> {
+            Arc::new(Lambertian::new(Color::new(r, g, b)))
+        };
+        let (red, white, green) = (
+            lambertian(0.65, 0.05, 0.05),
+            lambertian(0.73, 0.73, 0.73),
+            lambertian(0.12, 0.45, 0.15),
+        );
+        let light: Arc<dyn Material + Send + Sync> =
+            Arc::new(DiffuseLight::new_color(Color::new(15.0, 15.0, 15.0)));
+        let quad =
+            |q: [f64; 3], u: [f64; 3], v: [f64; 3], mat: &Arc<dyn Material + Send + Sync>| {
+                Arc::new(Quad::new(
+                    Point3::new(q[0], q[1], q[2]),
+                    Vec3::new(u[0], u[1], u[2]),
+                    Vec3::new(v[0], v[1], v[2]),
+                    mat.clone(),
+                ))
+            };
+
+        let mut world = HittableList::default();
+        world.add(quad(
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            &green,
+        ));
+        world.add(quad(
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            &red,
+        ));
+        world.add(quad(
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            &white,
+        ));
+        world.add(quad(
+            [1.0, 1.0, 1.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            &white,
+        ));
+        world.add(quad(
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            &white,
+        ));
+        world.add(quad(
+            [0.35, 0.999, 0.4],
+            [0.3, 0.0, 0.0],
+            [0.0, 0.0, 0.2],
+            &light,
+        ));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.35, 0.2, 0.4),
+            0.2,
+            Arc::new(Dielectric::new(1.5)),
+        )));
+
+        let mut lights = HittableList::default();
+        lights.add(quad(
+            [0.35, 0.999, 0.4],
+            [0.3, 0.0, 0.0],
+            [0.0, 0.0, 0.2],
+            &light,
+        ));
+
+        let cam = Camera {
+            image_width: 24,
+            samples_per_pixel: 16,
+            max_depth: 12,
+            vfov: 40.0,
+            lookfrom: Point3::new(0.5, 0.5, -1.4),
+            lookat: Point3::new(0.5, 0.5, 0.0),
+            threads: 1,
+            tile_size: 64, // 整张图只有一个分块，两种实现使用同一个随机数序列
+            ..Default::default()
+        };
+        (cam, Arc::new(world), Arc::new(lights))
+    }
+
+    fn render_iterative(seed: u64) -> Vec<Color> {
+        let (mut cam, world, lights) = scene();
+        cam.seed = Some(seed);
+        let framebuffer = cam.render_framebuffer(world, Some(lights));
+        (0..framebuffer.height())
+            .flat_map(|j| (0..framebuffer.width()).map(move |i| (i, j)))
+            .map(|(i, j)| framebuffer.get(i, j))
+            .collect()
+    }
+
+    fn render_reference(seed: u64) -> Vec<Color> {
+        let (mut cam, world, lights) = scene();
+        cam.initialize();
+        seed_thread_rng(mix_seed(seed, 0));
+        let mut pixels = Vec::new();
+        for j in 0..cam.image_height {
+            for i in 0..cam.image_width {
+                let mut pixel_color = Color::default();
+                for s_j in 0..cam.sqrt_spp {
+                    for s_i in 0..cam.sqrt_spp {
+                        let r = cam.get_ray(i, j, s_i, s_j);
+                        pixel_color += reference_ray_color(
+                            &cam,
+                            &r,
+                            cam.max_depth,
+                            &world,
+                            Some(&lights),
+                            None,
+                            Color::new(1.0, 1.0, 1.0),
+                        );
+                    }
+                }
+                pixels.push(pixel_color * cam.pixel_samples_scale);
+            }
+        }
+        pixels
+    }
+
+    // 所有像素所有通道的均值和方差
+    fn mean_and_variance(pixels: &[Color]) -> (f64, f64) {
+        let values: Vec<f64> = pixels.iter().flat_map(|c| [c.x(), c.y(), c.z()]).collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+        (mean, variance)
+    }
+
+    #[test]
+    fn iterative_matches_recursive_reference() {
+        // 同一个种子下两者消耗随机数的顺序相同，差别只来自浮点舍入
+        let iterative = render_iterative(7);
+        let reference = render_reference(7);
+        for (a, b) in iterative.iter().zip(&reference) {
+            assert!(
+                (*a - *b).length() <= 1e-9 * (1.0 + b.length()),
+                "{:?} vs {:?}",
+                a,
+                b
+            );
+        }
+
+        // 不同种子下两者是同一个估计量的独立样本，均值和方差在统计误差内一致
+        let (mean_a, variance_a) = mean_and_variance(&iterative);
+        let (mean_b, variance_b) = mean_and_variance(&render_reference(8));
+        assert!(mean_a > 0.0);
+        assert!(
+            (mean_a - mean_b).abs() < 0.05 * mean_a,
+            "{} vs {}",
+            mean_a,
+            mean_b
+        );
+        assert!(
+            (variance_a - variance_b).abs() < 0.25 * variance_a,
+            "{} vs {}",
+            variance_a,
+            variance_b
+        );
+    }
+
+    #[test]
+    fn absorption_covers_segments_ending_on_other_objects() {
+        // 半径 1 的吸收介质球里有一个半径 0.5 的发光球，界面折射率为 1，光线直线穿过。
+        // 从相机看到的光只在介质里走过 0.5，剩下 0.5^0.5
+        let mut glass = RoughDielectric::new(1.0, 0.0);
+        glass.set_absorption(Color::new(0.5, 0.5, 0.5), 1.0);
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::default(),
+            1.0,
+            Arc::new(glass),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::default(),
+            0.5,
+            Arc::new(DiffuseLight::new_color(Color::new(1.0, 1.0, 1.0))),
+        )));
+        let world: HittableRef = Arc::new(world);
+
+        let mut cam = Camera {
+            image_width: 1,
+            samples_per_pixel: 4,
+            vfov: 0.1,
+            lookfrom: Point3::new(0.0, 0.0, 5.0),
+            lookat: Point3::default(),
+            rr_depth: None,
+            seed: Some(1),
+            ..Default::default()
+        };
+        let color = cam.render_framebuffer(world, None).get(0, 0);
+        let expected = 0.5f64.sqrt();
+        assert!(
+            (color - Color::new(expected, expected, expected)).length() < 1e-4,
+            "{:?}",
+            color
+        );
+    }
+}
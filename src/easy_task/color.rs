@@ -1,42 +1,22 @@
-use crate::easy_task::interval::Interval;
 use crate::easy_task::vec3::Vec3;
-use std::fs::File;
-use std::io::Write;
 
 pub type Color = Vec3;
 
-impl Color {
-    pub fn write_color(&self, out: &mut File, pixel_color: Color) -> std::io::Result<()> {
-        let mut r = pixel_color.x();
-        let mut g = pixel_color.y();
-        let mut b = pixel_color.z();
-
-        if r.is_nan() {
-            r = 0.0;
-        }
-        if g.is_nan() {
-            g = 0.0;
-        }
-        if b.is_nan() {
-            b = 0.0;
-        }
-
-        r = linear_to_gamma(r);
-        g = linear_to_gamma(g);
-        b = linear_to_gamma(b);
-
-        let intensity = Interval::new(0.000, 0.999);
-        let rbyte = (256.0 * intensity.clamp(r)) as i32;
-        let gbyte = (256.0 * intensity.clamp(g)) as i32;
-        let bbyte = (256.0 * intensity.clamp(b)) as i32;
-        writeln!(out, "{} {} {}\n", rbyte, gbyte, bbyte,)
+// sRGB 标准的分段传递函数：暗部是线性段，其余是指数 1/2.4 的幂函数
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0 {
+        0.0
+    } else if linear_component <= 0.003_130_8 {
+        12.92 * linear_component
+    } else {
+        1.055 * linear_component.powf(1.0 / 2.4) - 0.055
     }
 }
 
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0.0 {
-        linear_component.sqrt()
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0.040_45 {
+        srgb_component / 12.92
     } else {
-        0.0
+        ((srgb_component + 0.055) / 1.055).powf(2.4)
     }
 }
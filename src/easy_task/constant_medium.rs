@@ -9,9 +9,14 @@ use super::texture::Texture;
 use super::vec3::Vec3;
 use std::sync::Arc;
 
+// 在密度为 density 的均匀介质中采样下一次碰撞前走过的距离，服从指数分布
+pub fn free_flight_distance(density: f64) -> f64 {
+    -rtweekend::random_double().ln() / density
+}
+
 pub struct ConstantMedium {
     boundary: Arc<dyn Hittable + Sync + Send>,
-    neg_inv_density: f64,
+    density: f64,
     phase_function: Arc<dyn Material + Sync + Send>,
 }
 
@@ -24,7 +29,7 @@ impl ConstantMedium {
     ) -> Self {
         Self {
             boundary,
-            neg_inv_density: -1.0 / density,
+            density,
             phase_function: Arc::new(Isotropic::new(a)),
         }
     }
@@ -36,10 +41,49 @@ impl ConstantMedium {
     ) -> Self {
         Self {
             boundary,
-            neg_inv_density: -1.0 / density,
+            density,
             phase_function: Arc::new(Isotropic::new_with_color(albedo)),
         }
     }
+
+    // 使用任意相函数，例如 HenyeyGreenstein
+    pub fn new_with_phase(
+        boundary: Arc<dyn Hittable + Sync + Send>,
+        density: f64,
+        phase_function: Arc<dyn Material + Sync + Send>,
+    ) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+// 光线在封闭边界内部的那一段与 ray_t 的交集，光线起点在边界内部时从 0 开始
+pub fn medium_segment(
+    boundary: &(dyn Hittable + Sync + Send),
+    r: &Ray,
+    ray_t: &Interval,
+) -> Option<(f64, f64)> {
+    let mut rec1 = HitRecord::default();
+    let mut rec2 = HitRecord::default();
+
+    if !boundary.hit(r, &interval::UNIVERSE, &mut rec1) {
+        return None;
+    }
+
+    if !boundary.hit(
+        r,
+        &Interval::new(rec1.t + 0.0001, rtweekend::INFINITY),
+        &mut rec2,
+    ) {
+        return None;
+    }
+
+    let t0 = rec1.t.max(ray_t.min).max(0.0);
+    let t1 = rec2.t.min(ray_t.max);
+    (t0 < t1).then_some((t0, t1))
 }
 
 impl Hittable for ConstantMedium {
@@ -48,49 +92,23 @@ impl Hittable for ConstantMedium {
         const ENABLE_DEBUG: bool = false;
         let debugging = ENABLE_DEBUG && rtweekend::random_double() < 0.00001;
 
-        let mut rec1 = HitRecord::default();
-        let mut rec2 = HitRecord::default();
-
-        if !self.boundary.hit(r, &interval::UNIVERSE, &mut rec1) {
+        let Some((t0, t1)) = medium_segment(self.boundary.as_ref(), r, ray_t) else {
             return false;
-        }
-
-        if !self.boundary.hit(
-            r,
-            &Interval::new(rec1.t + 0.0001, rtweekend::INFINITY),
-            &mut rec2,
-        ) {
-            return false;
-        }
+        };
 
         if debugging {
-            eprintln!("\nray_tmin={} ray_tmax={}", rec1.t, rec2.t);
-        }
-
-        if rec1.t < ray_t.min {
-            rec1.t = ray_t.min;
-        }
-        if rec2.t > ray_t.max {
-            rec2.t = ray_t.max;
-        }
-
-        if rec1.t >= rec2.t {
-            return false;
-        }
-
-        if rec1.t < 0.0 {
-            rec1.t = 0.0;
+            eprintln!("\nray_tmin={} ray_tmax={}", t0, t1);
         }
 
         let ray_length = r.direction().length();
-        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * rtweekend::random_double().ln();
+        let distance_inside_boundary = (t1 - t0) * ray_length;
+        let hit_distance = free_flight_distance(self.density);
 
         if hit_distance > distance_inside_boundary {
             return false;
         }
 
-        rec.t = rec1.t + hit_distance / ray_length;
+        rec.t = t0 + hit_distance / ray_length;
         rec.p = r.at(rec.t);
 
         if debugging {
@@ -106,6 +124,19 @@ impl Hittable for ConstantMedium {
         true
     }
 
+    fn shadow_hit(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    // 均匀介质的透过率有解析解
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let Some((t0, t1)) = medium_segment(self.boundary.as_ref(), r, ray_t) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+        let tr = (-self.density * (t1 - t0) * r.direction().length()).exp();
+        Color::new(tr, tr, tr)
+    }
+
     fn bounding_box(&self) -> &Aabb {
         self.boundary.bounding_box()
     }
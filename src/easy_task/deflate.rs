@@ -0,0 +1,332 @@
+// 最小的 zlib 压缩实现（RFC 1950/1951）：LZ77 哈希链匹配 + 固定 Huffman 编码，
+// 供 PNG 等输出格式使用
+
+const WINDOW_SIZE: usize = 1 << 15;
+const WINDOW_MASK: usize = WINDOW_SIZE - 1;
+const HASH_BITS: usize = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// 每个位置最多回溯的候选数，越大压缩率越高、速度越慢
+const MAX_CHAIN: usize = 48;
+const NO_POS: u32 = u32::MAX;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    // 普通数据按低位在前写入
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman 码要求高位在前，先把码字按位反转
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+// 固定 Huffman 表中的字面量/长度码
+fn write_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let l = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+    write_literal(writer, 257 + l as u32);
+    writer.write(
+        (length - LENGTH_BASE[l] as usize) as u32,
+        LENGTH_EXTRA[l] as u32,
+    );
+
+    let d = DIST_BASE.partition_point(|&base| base as usize <= distance) - 1;
+    writer.write_code(d as u32, 5);
+    writer.write(
+        (distance - DIST_BASE[d] as usize) as u32,
+        DIST_EXTRA[d] as u32,
+    );
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let v = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+// 输出不带 zlib 头的原始 deflate 数据流，整个输入编码成一个固定 Huffman 块
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new(Vec::with_capacity(data.len() / 2 + 16));
+    writer.write(1, 1); // BFINAL
+    writer.write(1, 2); // BTYPE = 01，固定 Huffman
+
+    let mut head = vec![NO_POS; 1 << HASH_BITS];
+    let mut prev = vec![NO_POS; WINDOW_SIZE];
+    let insert = |pos: usize, head: &mut [u32], prev: &mut [u32]| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(data, pos);
+            prev[pos & WINDOW_MASK] = head[h];
+            head[h] = pos as u32;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(data, pos)];
+            let mut chain = 0;
+            while candidate != NO_POS && chain < MAX_CHAIN {
+                let start = candidate as usize;
+                if pos - start > WINDOW_SIZE - 1 {
+                    break;
+                }
+                let len = data[start..start + max_len]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - start;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[start & WINDOW_MASK];
+                // 链表里的位置必须严格递减，否则说明环形缓冲区已被覆盖
+                if next == NO_POS || next as usize >= start {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut writer, best_len, best_dist);
+            for p in pos..pos + best_len {
+                insert(p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            write_literal(&mut writer, data[pos] as u32);
+            insert(pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 是保证 b 不溢出的最大分段长度
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+// 可以分段计算：crc32_update(crc32(a), b) == crc32(a ++ b)
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// zlib 格式：2 字节头 + deflate 数据 + 大端 Adler-32 校验
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 只认识固定 Huffman 块的解压器，用来检查 deflate 的输出能否还原
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+            self.pos += 1;
+            bit as u32
+        }
+
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |v, i| v | self.bit() << i)
+        }
+
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |v, _| v << 1 | self.bit())
+        }
+
+        fn symbol(&mut self) -> u32 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code;
+            }
+            let code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => 280 + code - 0xc0,
+                _ => 144 + (code << 1 | self.bit()) - 0x190,
+            }
+        }
+    }
+
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { data, pos: 0 };
+        assert_eq!(reader.bits(1), 1, "BFINAL");
+        assert_eq!(reader.bits(2), 1, "BTYPE");
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.symbol();
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let l = (symbol - 257) as usize;
+                    let length =
+                        LENGTH_BASE[l] as usize + reader.bits(LENGTH_EXTRA[l] as u32) as usize;
+                    let d = reader.code(5) as usize;
+                    let distance =
+                        DIST_BASE[d] as usize + reader.bits(DIST_EXTRA[d] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn round_trip(data: &[u8]) {
+        let z = zlib_compress(data);
+        assert_eq!(z[..2], [0x78, 0x01]);
+        assert_eq!(
+            u16::from_be_bytes([z[0], z[1]]) % 31,
+            0,
+            "zlib header check"
+        );
+        assert_eq!(inflate_fixed(&z[2..z.len() - 4]), data);
+        assert_eq!(z[z.len() - 4..], adler32(data).to_be_bytes());
+    }
+
+    #[test]
+    fn zlib_round_trip() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"abcabcabcabcabcabcabcabcabcabc");
+        round_trip(&[0u8; 1000]);
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        round_trip(&all_bytes);
+
+        // 伪随机但带有重复片段的数据，覆盖各种长度和超过窗口一半的距离
+        let mut state = 12345u32;
+        let mut data = Vec::new();
+        while data.len() < 100_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if state >> 31 == 0 && data.len() > 300 {
+                let distance = 1 + (state >> 8) as usize % data.len().min(WINDOW_SIZE - 1);
+                let length = 3 + (state >> 4) as usize % 300;
+                for _ in 0..length {
+                    data.push(data[data.len() - distance]);
+                }
+            } else {
+                data.push((state >> 16) as u8 % 16);
+            }
+        }
+        round_trip(&data);
+        assert!(deflate(&data).len() < data.len() / 2);
+    }
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), crc32(b"123456789"));
+        // PNG 文件结尾的 IEND 块的 CRC
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // 超过 5552 字节的分段边界后结果仍然正确
+        let long = vec![0xffu8; 100_000];
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in &long {
+            a = (a + byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(&long), (b << 16 | a) as u32);
+    }
+}
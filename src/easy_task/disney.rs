@@ -0,0 +1,387 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::hittable::HitRecord;
+use crate::easy_task::material::{Material, ScatterRecord};
+use crate::easy_task::microfacet::{TrowbridgeReitz, to_local};
+use crate::easy_task::onb::Onb;
+use crate::easy_task::pdf::Pdf;
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rough_dielectric::MicrofacetDielectric;
+use crate::easy_task::rtweekend::{PI, random_double};
+use crate::easy_task::texture::{SolidColor, Texture};
+use crate::easy_task::vec3::{Vec3, dot, random_cosine_direction, reflect, unit_vector};
+use std::sync::Arc;
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+fn lerp(t: f64, a: Color, b: Color) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+// Schlick 近似中的 (1 - cosθ)^5
+fn schlick_weight(cos_theta: f64) -> f64 {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0);
+    let m2 = m * m;
+    m2 * m2 * m
+}
+
+// 清漆层使用的 GTR1（Berry）分布，比 GGX 拖尾更长
+fn gtr1(cos_h: f64, alpha: f64) -> f64 {
+    if cos_h <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_h * cos_h;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+fn sample_gtr1(alpha: f64) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos2 = (1.0 - a2.powf(1.0 - random_double())) / (1.0 - a2);
+    let cos_theta = cos2.clamp(0.0, 1.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * random_double();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// 在某个着色点上取出纹理值后的 BSDF，方向都在以法线为 +z 的局部坐标中，
+// 各向异性的高光沿局部 x 轴拉长
+#[derive(Debug, Clone, Copy)]
+struct DisneyLobes {
+    base_color: Color,
+    roughness: f64,
+    sheen: Color,              // 已乘上 sheen 强度和色调
+    specular_color: Color,     // 正入射时的高光反射率
+    diffuse_weight: f64,       // (1 - metallic)(1 - transmission)
+    transmission_weight: f64,  // (1 - metallic) transmission
+    transmission_color: Color, // 每次穿过界面乘一次，进出一趟正好是 base_color
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    specular_lobe: TrowbridgeReitz,
+    clearcoat_lobe: TrowbridgeReitz,
+    glass: MicrofacetDielectric,
+}
+
+impl DisneyLobes {
+    // 各个波瓣被选中采样的概率，大致正比于它们从 wo 方向看的反射率
+    fn weights(&self, wo: Vec3) -> Option<[f64; 4]> {
+        let fv = schlick_weight(wo.z());
+        let weights = [
+            self.diffuse_weight * (luminance(self.base_color) + luminance(self.sheen)),
+            luminance(lerp(fv, self.specular_color, Color::new(1.0, 1.0, 1.0))),
+            0.25 * self.clearcoat * (0.04 + 0.96 * fv),
+            self.transmission_weight * luminance(self.transmission_color),
+        ];
+        let total: f64 = weights.iter().sum();
+        (total > 0.0).then(|| weights.map(|w| w / total))
+    }
+
+    // f(wo, wi)·|cosθi|
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z() == 0.0 {
+            return Color::default();
+        }
+        // 从内部射出的光线只会遇到玻璃界面
+        if wo.z() < 0.0 {
+            let f = self.glass.eval(wo, wi);
+            return if wi.z() > 0.0 {
+                f * self.transmission_color
+            } else {
+                Color::new(f, f, f)
+            };
+        }
+        if wi.z() <= 0.0 {
+            return self.transmission_weight * self.glass.eval(wo, wi) * self.transmission_color;
+        }
+
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        let wh = unit_vector(wo + wi);
+        let cos_d = dot(wi, wh);
+        let fl = schlick_weight(cos_i);
+        let fv = schlick_weight(cos_o);
+        let fd = schlick_weight(cos_d);
+
+        // Burley 漫反射，粗糙表面在掠射角有回射
+        let rr = 2.0 * self.roughness * cos_d * cos_d;
+        let diffuse = (1.0 - fl / 2.0) * (1.0 - fv / 2.0) + rr * (fl + fv + fl * fv * (rr - 1.0));
+        let diffuse = diffuse / PI * self.base_color + fd * self.sheen;
+
+        let f = lerp(fd, self.specular_color, Color::new(1.0, 1.0, 1.0));
+        let specular =
+            self.specular_lobe.d(wh) * self.specular_lobe.g(wo, wi) / (4.0 * cos_o * cos_i) * f;
+
+        let clearcoat = 0.25
+            * self.clearcoat
+            * gtr1(wh.z(), self.clearcoat_alpha)
+            * (0.04 + 0.96 * fd)
+            * self.clearcoat_lobe.g(wo, wi)
+            / (4.0 * cos_o * cos_i);
+
+        (self.diffuse_weight * diffuse + specular + Color::new(clearcoat, clearcoat, clearcoat))
+            * cos_i
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() == 0.0 {
+            return 0.0;
+        }
+        if wo.z() < 0.0 {
+            return self.glass.pdf(wo, wi);
+        }
+        let Some([diffuse, specular, clearcoat, transmission]) = self.weights(wo) else {
+            return 0.0;
+        };
+        let mut pdf = transmission * self.glass.pdf(wo, wi);
+        if wi.z() > 0.0 {
+            pdf += diffuse * wi.z() / PI;
+            let wh = unit_vector(wo + wi);
+            let cos_oh = dot(wo, wh);
+            if cos_oh > 0.0 {
+                pdf += specular * self.specular_lobe.visible_pdf(wo, wh) / (4.0 * cos_oh);
+                pdf += clearcoat * gtr1(wh.z(), self.clearcoat_alpha) * wh.z() / (4.0 * cos_oh);
+            }
+        }
+        pdf
+    }
+
+    fn sample(&self, wo: Vec3) -> Option<Vec3> {
+        if wo.z() < 0.0 {
+            return self.glass.sample(wo);
+        }
+        let [diffuse, specular, clearcoat, _] = self.weights(wo)?;
+        let u = random_double();
+        if u < diffuse {
+            return Some(random_cosine_direction());
+        }
+        let wm = if u < diffuse + specular {
+            self.specular_lobe.sample_visible_normal(wo)
+        } else if u < diffuse + specular + clearcoat {
+            sample_gtr1(self.clearcoat_alpha)
+        } else {
+            return self.glass.sample(wo);
+        };
+        let wi = reflect(-wo, wm);
+        (wi.z() > 0.0).then_some(wi)
+    }
+}
+
+pub struct DisneyPdf {
+    uvw: Onb,
+    wo: Vec3,
+    lobes: DisneyLobes,
+}
+
+impl Pdf for DisneyPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.lobes
+            .pdf(self.wo, to_local(&self.uvw, unit_vector(direction)))
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.lobes
+            .sample(self.wo)
+            .map_or(Vec3::default(), |wi| self.uvw.transform(wi))
+    }
+}
+
+// Disney principled BSDF（Burley 2012/2015）：一组直观的参数覆盖漫反射、金属、
+// 塑料、玻璃和车漆等常见材质。每个参数都可以由纹理驱动，标量参数取纹理的第一个分量
+pub struct Disney {
+    pub base_color: Arc<dyn Texture + Send + Sync>,
+    pub metallic: Arc<dyn Texture + Send + Sync>,
+    pub roughness: Arc<dyn Texture + Send + Sync>,
+    pub specular: Arc<dyn Texture + Send + Sync>, // 0.5 对应折射率 1.5 的 4% 反射率
+    pub specular_tint: Arc<dyn Texture + Send + Sync>,
+    pub sheen: Arc<dyn Texture + Send + Sync>,
+    pub sheen_tint: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat_gloss: Arc<dyn Texture + Send + Sync>,
+    pub transmission: Arc<dyn Texture + Send + Sync>,
+    pub anisotropic: Arc<dyn Texture + Send + Sync>,
+}
+
+impl Disney {
+    pub fn new(base_color: Arc<dyn Texture + Send + Sync>) -> Self {
+        let constant = |x: f64| -> Arc<dyn Texture + Send + Sync> {
+            Arc::new(SolidColor::new(Color::new(x, x, x)))
+        };
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            sheen_tint: constant(0.5),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            anisotropic: constant(0.0),
+        }
+    }
+
+    fn lobes(&self, rec: &HitRecord) -> DisneyLobes {
+        let scalar = |tex: &Arc<dyn Texture + Send + Sync>| {
+            tex.value(rec.u, rec.v, rec.p).x().clamp(0.0, 1.0)
+        };
+        let base_color = self.base_color.value(rec.u, rec.v, rec.p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let specular = scalar(&self.specular);
+        let transmission = scalar(&self.transmission);
+
+        // 去掉亮度后的基础色调
+        let lum = luminance(base_color);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let tint = if lum > 0.0 { base_color / lum } else { white };
+
+        let dielectric_specular = 0.08 * specular * lerp(scalar(&self.specular_tint), white, tint);
+        // 与 specular 对应的正入射反射率换算出透射用的折射率
+        let r0 = (0.08 * specular).sqrt();
+        let eta = (1.0 + r0) / (1.0 - r0);
+        let specular_lobe = TrowbridgeReitz::new_anisotropic(roughness, scalar(&self.anisotropic));
+
+        DisneyLobes {
+            base_color,
+            roughness,
+            sheen: scalar(&self.sheen) * lerp(scalar(&self.sheen_tint), white, tint),
+            specular_color: lerp(metallic, dielectric_specular, base_color),
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            transmission_weight: (1.0 - metallic) * transmission,
+            transmission_color: Color::new(
+                base_color.x().max(0.0).sqrt(),
+                base_color.y().max(0.0).sqrt(),
+                base_color.z().max(0.0).sqrt(),
+            ),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * scalar(&self.clearcoat_gloss),
+            specular_lobe,
+            // 清漆的法线分布是上面的 GTR1，这个 GGX 只提供遮蔽项 G。Burley 固定使用 α = 0.25，
+            // TrowbridgeReitz::new 会把粗糙度平方，所以这里传 0.5
+            clearcoat_lobe: TrowbridgeReitz::new(0.5),
+            glass: MicrofacetDielectric::new(eta, specular_lobe),
+        }
+    }
+
+    // 有透射时以外法线为 z 轴，wo 在下半球表示光线在物体内部；
+    // 否则按双面材质处理，始终使用朝向光线一侧的法线
+    fn frame(lobes: &DisneyLobes, rec: &HitRecord) -> Onb {
+        let inside = !rec.front_face && lobes.transmission_weight > 0.0;
+        Onb::new_from_w(if inside { -rec.normal } else { rec.normal })
+    }
+}
+
+impl Material for Disney {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let lobes = self.lobes(rec);
+        let uvw = Self::frame(&lobes, rec);
+        let wo = to_local(&uvw, -unit_vector(r_in.direction()));
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.skip_pdf = false;
+        srec.pdf = Box::new(DisneyPdf { uvw, wo, lobes });
+        true
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let lobes = self.lobes(rec);
+        let uvw = Self::frame(&lobes, rec);
+        let wo = to_local(&uvw, -unit_vector(r_in.direction()));
+        let wi = to_local(&uvw, unit_vector(scattered.direction()));
+        lobes.pdf(wo, wi)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let lobes = self.lobes(rec);
+        let uvw = Self::frame(&lobes, rec);
+        let wo = to_local(&uvw, -unit_vector(r_in.direction()));
+        let wi = to_local(&uvw, unit_vector(scattered.direction()));
+        lobes.eval(wo, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::material::sampling_integrals;
+    use crate::easy_task::pdf::integrate_sphere;
+    use crate::easy_task::vec3::Point3;
+
+    fn constant(x: f64) -> Arc<dyn Texture + Send + Sync> {
+        Arc::new(SolidColor::new(Color::new(x, x, x)))
+    }
+
+    // (metallic, roughness, clearcoat, clearcoat_gloss, transmission, anisotropic)
+    const PARAMETERS: [[f64; 6]; 5] = [
+        [0.0, 0.5, 0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.3, 0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.4, 1.0, 0.3, 0.0, 0.0],
+        [0.0, 0.35, 0.0, 1.0, 1.0, 0.0],
+        [0.3, 0.6, 0.5, 0.5, 0.5, 0.8],
+    ];
+
+    fn material(p: [f64; 6]) -> Disney {
+        let mut disney = Disney::new(Arc::new(SolidColor::new(Color::new(0.8, 0.5, 0.3))));
+        disney.metallic = constant(p[0]);
+        disney.roughness = constant(p[1]);
+        disney.clearcoat = constant(p[2]);
+        disney.clearcoat_gloss = constant(p[3]);
+        disney.transmission = constant(p[4]);
+        disney.anisotropic = constant(p[5]);
+        disney
+    }
+
+    fn directions() -> [Vec3; 3] {
+        [
+            Vec3::new(0.0, 0.0, 1.0),
+            unit_vector(Vec3::new(0.3, 0.2, 0.9)),
+            unit_vector(Vec3::new(0.9, 0.1, 0.2)),
+        ]
+    }
+
+    #[test]
+    fn lobe_weights_sum_to_one() {
+        for p in PARAMETERS {
+            let lobes = material(p).lobes(&HitRecord::default());
+            for wo in directions() {
+                let weights = lobes.weights(wo).unwrap();
+                assert!(weights.iter().all(|&w| w >= 0.0));
+                assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12, "{:?}", p);
+            }
+        }
+    }
+
+    // 采样可能落到无效方向，所以 ∫ pdf dω 可以小于 1，但不能超过 1
+    #[test]
+    fn pdf_integrates_to_at_most_one() {
+        for p in PARAMETERS {
+            let lobes = material(p).lobes(&HitRecord::default());
+            for wo in directions() {
+                let integral = integrate_sphere(300_000, |wi| lobes.pdf(wo, wi));
+                assert!(integral <= 1.01, "{:?} {:?}: {}", p, wo, integral);
+                assert!(integral > 0.9, "{:?} {:?}: {}", p, wo, integral);
+            }
+        }
+    }
+
+    // 整个材质上按 srec.pdf 采样估计的 ∫ f·cosθ dω 与球面积分一致，说明 generate 服从 value；
+    // scattering_pdf 在球面上的积分同样不超过 1
+    #[test]
+    fn sampling_follows_pdf() {
+        let r_in = Ray::new(Point3::new(-1.0, 1.0, 0.3), Vec3::new(1.0, -1.0, -0.3));
+        for p in PARAMETERS {
+            let disney = material(p);
+            for front_face in [true, false] {
+                let rec = HitRecord {
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                    front_face,
+                    ..Default::default()
+                };
+                let [estimate, integral, pdf] = sampling_integrals(&disney, &r_in, &rec);
+                let case = format!("{p:?} {front_face}: {estimate} {integral} {pdf}");
+                assert!((estimate - integral).abs() < 0.01, "{case}");
+                assert!(pdf <= 1.01, "{case}");
+            }
+        }
+    }
+}
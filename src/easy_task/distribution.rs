@@ -0,0 +1,162 @@
+// 分段常数的一维分布，用于按亮度等权重做重要性采样
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64, // func 在 [0, 1] 上的积分
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].max(0.0) / n as f64;
+        }
+        let integral = cdf[n];
+        // 全为 0 时退化成均匀分布
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // 由 [0, 1) 上的均匀随机数得到 [0, 1) 上的样本，同时返回样本处的概率密度和所在的分段
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.count();
+        let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = ((offset as f64 + du) / n as f64).min(1.0 - f64::EPSILON);
+        (x, self.pdf(offset), offset)
+    }
+
+    // 第 index 个分段上的概率密度（相对 [0, 1] 上的测度）
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[index].max(0.0) / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// 二维分布：先按每行的积分选行，再在行内按条件分布选列
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // func 按行优先排列，共 height 行、每行 width 个值
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = func
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|r| r.integral()).collect());
+        Self { rows, marginal }
+    }
+
+    // 返回 (x, y) ∈ [0, 1)²，y 是行方向的坐标，以及该点的概率密度
+    pub fn sample(&self, u1: f64, u2: f64) -> (f64, f64, f64) {
+        let (y, pdf_y, row) = self.marginal.sample(u2);
+        let (x, pdf_x, _) = self.rows[row].sample(u1);
+        (x, y, pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let row = ((y * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        let columns = &self.rows[row];
+        let column = ((x * columns.count() as f64) as usize).min(columns.count() - 1);
+        self.marginal.pdf(row) * columns.pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_1d_follow_pdf() {
+        let dist = Distribution1D::new(vec![1.0, 0.0, 3.0, 4.0]);
+        assert_eq!(dist.integral(), 2.0);
+        let total: f64 = (0..dist.count()).map(|i| dist.pdf(i) / 4.0).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        // 分层的均匀随机数落在各分段的比例应该等于 pdf 乘分段宽度
+        let n = 100_000;
+        let mut counts = [0usize; 4];
+        for k in 0..n {
+            let (x, pdf, index) = dist.sample((k as f64 + 0.5) / n as f64);
+            assert_eq!(index, (x * 4.0) as usize);
+            assert_eq!(pdf, dist.pdf(index));
+            assert!(pdf > 0.0, "sampled a segment with zero weight");
+            counts[index] += 1;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            let expected = dist.pdf(i) / 4.0;
+            assert!((count as f64 / n as f64 - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn zero_function_is_uniform() {
+        let dist = Distribution1D::new(vec![0.0; 5]);
+        for k in 0..5 {
+            let (x, pdf, index) = dist.sample((k as f64 + 0.5) / 5.0);
+            assert_eq!(index, k);
+            assert!((x - (k as f64 + 0.5) / 5.0).abs() < 1e-12);
+            assert_eq!(pdf, 1.0);
+        }
+    }
+
+    #[test]
+    fn samples_2d_follow_pdf() {
+        let (width, height) = (3, 2);
+        let func = [1.0, 2.0, 0.0, 0.5, 0.0, 4.0];
+        let dist = Distribution2D::new(&func, width, height);
+        let sum: f64 = func.iter().sum();
+
+        let n = 300;
+        let mut counts = [0usize; 6];
+        for i in 0..n {
+            for j in 0..n {
+                let u1 = (i as f64 + 0.5) / n as f64;
+                let u2 = (j as f64 + 0.5) / n as f64;
+                let (x, y, pdf) = dist.sample(u1, u2);
+                assert!((pdf - dist.pdf(x, y)).abs() < 1e-12);
+                let cell = (y * height as f64) as usize * width + (x * width as f64) as usize;
+                // 密度与函数值成正比，并且在 [0, 1]² 上积分为 1
+                assert!((pdf - func[cell] * (width * height) as f64 / sum).abs() < 1e-12);
+                counts[cell] += 1;
+            }
+        }
+        for (cell, &count) in counts.iter().enumerate() {
+            let fraction = count as f64 / (n * n) as f64;
+            assert!((fraction - func[cell] / sum).abs() < 2e-3, "cell {}", cell);
+        }
+    }
+}
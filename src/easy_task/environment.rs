@@ -0,0 +1,102 @@
+use crate::easy_task::aabb::{self, Aabb};
+use crate::easy_task::color::Color;
+use crate::easy_task::distribution::Distribution2D;
+use crate::easy_task::hittable::{HitRecord, Hittable, Sphere};
+use crate::easy_task::interval::Interval;
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rtw_image::HdrImage;
+use crate::easy_task::rtweekend::{PI, degrees_to_radians, random_double};
+use crate::easy_task::vec3::{Point3, Vec3, unit_vector};
+use std::fmt::Debug;
+
+// 无限远处的光照：光线没有击中任何物体时，由它给出该方向上的辐射度
+pub trait Environment: Debug {
+    fn radiance(&self, direction: Vec3) -> Color;
+}
+
+// 等距柱状投影的环境贴图，按亮度做重要性采样。
+// 它实现 Hittable 只是为了放进 lights 列表参与光源采样，hit 永远返回 false，不要加入场景本身
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    image: HdrImage,
+    intensity: f64,
+    rotation: f64, // 绕 y 轴旋转的角度，换算成 u 方向的偏移
+    distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    pub fn new(image: HdrImage, intensity: f64, rotation_degrees: f64) -> Self {
+        let (width, height) = (image.width(), image.height());
+        // 每个像素的权重是亮度乘以 sinθ，抵消两极附近像素对应立体角更小的影响
+        let mut func = Vec::with_capacity(width * height);
+        for j in 0..height {
+            let sin_theta = (PI * (j as f64 + 0.5) / height as f64).sin();
+            for i in 0..width {
+                let c = image.pixel(i, j);
+                let luminance = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+                func.push(luminance.max(0.0) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+        Self {
+            image,
+            intensity,
+            rotation: degrees_to_radians(rotation_degrees) / (2.0 * PI),
+            distribution,
+        }
+    }
+
+    // 方向到贴图坐标的映射和球面纹理坐标一致，x、y 都在 [0, 1) 内，y = 0 是图像顶部（+y 方向）
+    fn direction_to_image(&self, direction: Vec3) -> (f64, f64) {
+        let (u, v) = Sphere::get_sphere_uv(unit_vector(direction));
+        ((u - self.rotation).rem_euclid(1.0), 1.0 - v)
+    }
+
+    fn image_to_direction(&self, x: f64, y: f64) -> Vec3 {
+        let phi = 2.0 * PI * (x + self.rotation);
+        let theta = PI * (1.0 - y);
+        Vec3::new(
+            -theta.sin() * phi.cos(),
+            -theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    // 贴图坐标上的密度换算到立体角上：dω = 2π² sinθ dx dy
+    fn direction_pdf(&self, x: f64, y: f64) -> f64 {
+        let sin_theta = (PI * y).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(x, y) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+impl Environment for EnvironmentLight {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (x, y) = self.direction_to_image(direction);
+        let i = (x * self.image.width() as f64) as usize;
+        let j = (y * self.image.height() as f64) as usize;
+        self.intensity * self.image.pixel(i, j)
+    }
+}
+
+impl Hittable for EnvironmentLight {
+    fn hit(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &aabb::EMPTY
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        let (x, y) = self.direction_to_image(direction);
+        self.direction_pdf(x, y)
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        let (x, y, _) = self.distribution.sample(random_double(), random_double());
+        self.image_to_direction(x, y)
+    }
+}
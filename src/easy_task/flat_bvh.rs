@@ -0,0 +1,214 @@
+use crate::easy_task::aabb::{self, Aabb};
+use crate::easy_task::bvh_node::{BvhNode, BvhSplit};
+use crate::easy_task::color::Color;
+use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::hittable_list::HittableList;
+use crate::easy_task::interval::Interval;
+use crate::easy_task::ray::Ray;
+use std::sync::Arc;
+
+const MAX_LEAF_SIZE: usize = 2;
+const STACK_SIZE: usize = 128;
+// 超过这个深度后改用中位数划分，保证树高不超过遍历栈的大小
+const MAX_SAH_DEPTH: usize = 64;
+
+// 内部节点的左孩子紧跟在自己后面，只需记录右孩子的下标；
+// 叶子节点记录它在 primitives 中的起始位置和个数
+#[derive(Debug, Clone, Copy)]
+struct FlatNode {
+    bbox: Aabb,
+    offset: u32, // 叶子：第一个物体的下标；内部节点：右孩子的下标
+    count: u16,  // 0 表示内部节点
+    axis: u8,
+}
+
+pub struct FlatBvh {
+    nodes: Vec<FlatNode>,
+    primitives: Vec<Arc<dyn Hittable + Send + Sync>>,
+}
+
+impl FlatBvh {
+    #[allow(dead_code)]
+    pub fn new(list: &HittableList) -> Self {
+        Self::new_with(list, BvhSplit::default())
+    }
+
+    pub fn new_with(list: &HittableList, split: BvhSplit) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * list.objects.len()),
+            primitives: list.objects.clone(),
+        };
+        if !bvh.primitives.is_empty() {
+            let mut primitives = std::mem::take(&mut bvh.primitives);
+            bvh.build(&mut primitives, 0, 0, split);
+            bvh.primitives = primitives;
+        }
+        bvh
+    }
+
+    // 深度优先地把节点写进数组，返回这个子树根节点的下标
+    fn build(
+        &mut self,
+        primitives: &mut [Arc<dyn Hittable + Send + Sync>],
+        offset: usize,
+        depth: usize,
+        split: BvhSplit,
+    ) -> usize {
+        let mut bbox = aabb::EMPTY;
+        for object in primitives.iter() {
+            bbox = Aabb::new_aabb(&bbox, object.bounding_box());
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(FlatNode {
+            bbox,
+            offset: offset as u32,
+            count: primitives.len() as u16,
+            axis: 0,
+        });
+        if primitives.len() <= MAX_LEAF_SIZE {
+            return index;
+        }
+
+        let (mid, axis) = match split {
+            BvhSplit::Sah if depth < MAX_SAH_DEPTH => BvhNode::partition_sah(primitives),
+            _ => None,
+        }
+        .unwrap_or_else(|| BvhNode::partition_median(primitives));
+
+        let (left, right) = primitives.split_at_mut(mid);
+        self.build(left, offset, depth + 1, split);
+        let second = self.build(right, offset + mid, depth + 1, split);
+
+        let node = &mut self.nodes[index];
+        node.offset = second as u32;
+        node.count = 0;
+        node.axis = axis as u8;
+        index
+    }
+
+    #[allow(dead_code)]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+// 用预先算好的方向倒数做 slab 测试
+fn hit_bbox(
+    bbox: &Aabb,
+    origin: [f64; 3],
+    inv_dir: [f64; 3],
+    mut t_min: f64,
+    mut t_max: f64,
+) -> bool {
+    for axis in 0..3 {
+        let interval = bbox.axis_interval(axis);
+        let mut t0 = (interval.min - origin[axis]) * inv_dir[axis];
+        let mut t1 = (interval.max - origin[axis]) * inv_dir[axis];
+        if inv_dir[axis] < 0.0 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        t_min = t_min.max(t0);
+        t_max = t_max.min(t1);
+        if t_max <= t_min {
+            return false;
+        }
+    }
+    true
+}
+
+impl FlatBvh {
+    // 按由近到远的顺序访问包围盒与 [ray_t.min, closest] 相交的叶子中的物体。
+    // visit 返回 Some(t) 表示在 t 处找到了交点，之后只需要搜索更近的部分
+    fn traverse(
+        &self,
+        r: &Ray,
+        ray_t: &Interval,
+        mut visit: impl FnMut(&(dyn Hittable + Send + Sync), &Interval) -> Option<f64>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let origin = r.origin().e;
+        let dir = r.direction().e;
+        let inv_dir = [1.0 / dir[0], 1.0 / dir[1], 1.0 / dir[2]];
+
+        let mut closest = ray_t.max;
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if hit_bbox(&node.bbox, origin, inv_dir, ray_t.min, closest) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for object in &self.primitives[start..start + node.count as usize] {
+                        if let Some(t) = visit(object.as_ref(), &Interval::new(ray_t.min, closest))
+                        {
+                            closest = t;
+                        }
+                    }
+                } else {
+                    // 光线沿切分轴的负方向前进时，右孩子更近，先访问它
+                    let (near, far) = if dir[node.axis as usize] < 0.0 {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+}
+
+impl Hittable for FlatBvh {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        self.traverse(r, ray_t, |object, t| {
+            object.hit(r, t, rec).then(|| {
+                hit_anything = true;
+                rec.t
+            })
+        });
+        hit_anything
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        self.traverse(r, ray_t, |object, t| {
+            object.shadow_hit(r, t, rec).then(|| {
+                hit_anything = true;
+                rec.t
+            })
+        });
+        hit_anything
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let mut tr = Color::new(1.0, 1.0, 1.0);
+        self.traverse(r, ray_t, |object, t| {
+            tr = tr * object.transmittance(r, t);
+            None
+        });
+        tr
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        match self.nodes.first() {
+            Some(root) => &root.bbox,
+            None => &aabb::EMPTY,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::scheduler::Tile;
+
+// 渲染结果，按行优先保存每个像素的线性颜色（已经除以采样数）
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Color {
+        self.pixels[j * self.width + i]
+    }
+
+    #[allow(dead_code)]
+    pub fn set(&mut self, i: usize, j: usize, color: Color) {
+        self.pixels[j * self.width + i] = color;
+    }
+
+    // 把一个分块的像素（同样按行优先排列）拷贝到对应位置
+    pub fn write_tile(&mut self, tile: &Tile, pixels: &[Color]) {
+        let tile_width = tile.width();
+        for (row, chunk) in pixels.chunks(tile_width).enumerate() {
+            let start = (tile.y0 + row) * self.width + tile.x0;
+            self.pixels[start..start + tile_width].copy_from_slice(chunk);
+        }
+    }
+}
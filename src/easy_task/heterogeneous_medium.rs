@@ -0,0 +1,423 @@
+use crate::easy_task::aabb::Aabb;
+use crate::easy_task::color::Color;
+use crate::easy_task::constant_medium::{free_flight_distance, medium_segment};
+use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::interval::Interval;
+use crate::easy_task::material::{Isotropic, Material};
+use crate::easy_task::perlin::Perlin;
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rtweekend::random_double;
+use crate::easy_task::texture::Texture;
+use crate::easy_task::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+// 非均匀介质的密度场
+pub trait DensityField {
+    fn density(&self, p: Point3) -> f64;
+
+    // 整个场的密度上界，delta tracking 按这个密度采样候选碰撞点
+    fn max_density(&self) -> f64;
+}
+
+// 规则体素网格，数值位于每个体素的中心，之间三线性插值，网格外的密度为 0
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    min: Point3,
+    size: Vec3,
+    data: Vec<f32>, // x 变化最快，其次是 y、z
+    max: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], min: Point3, max: Point3, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), resolution.iter().product::<usize>());
+        let max_value = data.iter().fold(0.0f32, |m, &d| m.max(d)) as f64;
+        Self {
+            resolution,
+            min,
+            size: max - min,
+            data,
+            max: max_value,
+        }
+    }
+
+    // 读取 Mitsuba 的 .vol 格式：'V' 'O' 'L' 3，编码（1 表示 float32），
+    // 三个方向的分辨率，通道数，包围盒的最小、最大角，之后是小端序的体素数据。
+    // 只支持单通道的 float32 数据，scale 会乘到每个体素上
+    pub fn load(path: &str, scale: f64) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let error = |message: &str| format!("{}: {}", path, message);
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" {
+            return Err(error("not a .vol file"));
+        }
+        if bytes[3] != 3 {
+            return Err(error(&format!("unsupported version {}", bytes[3])));
+        }
+        let word = |i: usize| -> [u8; 4] { bytes[4 + 4 * i..8 + 4 * i].try_into().unwrap() };
+        let int = |i: usize| i32::from_le_bytes(word(i));
+        let float = |i: usize| f32::from_le_bytes(word(i)) as f64;
+
+        if int(0) != 1 {
+            return Err(error("only float32 data is supported"));
+        }
+        if int(4) != 1 {
+            return Err(error("only single-channel data is supported"));
+        }
+        if int(1) < 1 || int(2) < 1 || int(3) < 1 {
+            return Err(error("resolution must be positive"));
+        }
+        let resolution = [int(1) as usize, int(2) as usize, int(3) as usize];
+        let min = Point3::new(float(5), float(6), float(7));
+        let max = Point3::new(float(8), float(9), float(10));
+        if (0..3).any(|i| max[i] <= min[i]) {
+            return Err(error("bounding box is empty"));
+        }
+
+        let count = resolution.iter().product::<usize>();
+        let body = &bytes[48..];
+        if body.len() != 4 * count {
+            return Err(error(&format!(
+                "expected {} voxels, found {} bytes of data",
+                count,
+                body.len()
+            )));
+        }
+        let data = body
+            .chunks_exact(4)
+            .map(|b| (f32::from_le_bytes(b.try_into().unwrap()) as f64 * scale).max(0.0) as f32)
+            .collect();
+        Ok(Self::new(resolution, min, max, data))
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(z * ny + y) * nx + x] as f64
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: Point3) -> f64 {
+        let mut base = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let u = (p[axis] - self.min[axis]) / self.size[axis];
+            if !(0.0..=1.0).contains(&u) {
+                return 0.0;
+            }
+            // 体素中心位于 (i + 0.5) / n，边界外的一半体素取最近的值
+            let n = self.resolution[axis];
+            let g = (u * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            base[axis] = (g.floor() as usize).min(n.saturating_sub(2));
+            frac[axis] = g - base[axis] as f64;
+        }
+
+        let mut d = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0usize; 3];
+            for axis in 0..3 {
+                let upper = (corner >> axis) & 1 == 1;
+                let i = (base[axis] + upper as usize).min(self.resolution[axis] - 1);
+                index[axis] = i;
+                weight *= if upper { frac[axis] } else { 1.0 - frac[axis] };
+            }
+            if weight > 0.0 {
+                d += weight * self.voxel(index[0], index[1], index[2]);
+            }
+        }
+        d
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+// 用 Perlin 湍流生成的烟雾：湍流值截断到 [0, 1] 后乘以 density
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    density: f64,
+}
+
+impl NoiseDensity {
+    pub fn new(density: f64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::default(),
+            scale,
+            density,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: Point3) -> f64 {
+        self.density * self.noise.turb(self.scale * p, 7).min(1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.density
+    }
+}
+
+// 密度随位置变化的参与介质，边界的用法和 ConstantMedium 相同。
+// 碰撞点用 delta tracking 采样，阴影光线的透过率用 ratio tracking 估计，两者都是无偏的
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable + Sync + Send>,
+    field: Arc<dyn DensityField + Sync + Send>,
+    phase_function: Arc<dyn Material + Sync + Send>,
+}
+
+impl HeterogeneousMedium {
+    #[allow(dead_code)]
+    pub fn new(
+        boundary: Arc<dyn Hittable + Sync + Send>,
+        field: Arc<dyn DensityField + Sync + Send>,
+        a: Arc<dyn Texture + Sync + Send>,
+    ) -> Self {
+        Self::new_with_phase(boundary, field, Arc::new(Isotropic::new(a)))
+    }
+
+    pub fn new_with_phase(
+        boundary: Arc<dyn Hittable + Sync + Send>,
+        field: Arc<dyn DensityField + Sync + Send>,
+        phase_function: Arc<dyn Material + Sync + Send>,
+    ) -> Self {
+        Self {
+            boundary,
+            field,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let majorant = self.field.max_density();
+        if majorant <= 0.0 {
+            return false;
+        }
+        let Some((t0, t1)) = medium_segment(self.boundary.as_ref(), r, ray_t) else {
+            return false;
+        };
+
+        // 按上界密度采样候选碰撞点，以 σ(p)/σ_max 的概率接受为真实碰撞，否则继续前进
+        let ray_length = r.direction().length();
+        let mut t = t0;
+        loop {
+            t += free_flight_distance(majorant) / ray_length;
+            if t >= t1 {
+                return false;
+            }
+            if random_double() * majorant < self.field.density(r.at(t)) {
+                break;
+            }
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.mat = Some(Arc::clone(&self.phase_function));
+        true
+    }
+
+    fn shadow_hit(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    // 每个候选碰撞点把透过率乘以 1 - σ(p)/σ_max，变得很小后用俄罗斯轮盘赌提前结束
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let majorant = self.field.max_density();
+        let Some((t0, t1)) =
+            medium_segment(self.boundary.as_ref(), r, ray_t).filter(|_| majorant > 0.0)
+        else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+
+        let ray_length = r.direction().length();
+        let mut tr = 1.0;
+        let mut t = t0;
+        loop {
+            t += free_flight_distance(majorant) / ray_length;
+            if t >= t1 {
+                break;
+            }
+            tr *= 1.0 - self.field.density(r.at(t)) / majorant;
+            if tr < 0.1 {
+                if random_double() < 0.5 {
+                    return Color::default();
+                }
+                tr *= 2.0;
+            }
+        }
+        Color::new(tr, tr, tr)
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::constant_medium::ConstantMedium;
+    use crate::easy_task::hittable::Sphere;
+    use crate::easy_task::material::Lambertian;
+
+    // 按 .vol 格式编码单通道 float32 的网格
+    fn vol(resolution: [i32; 3], min: [f32; 3], max: [f32; 3], data: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL".to_vec();
+        bytes.push(3);
+        bytes.extend(1i32.to_le_bytes());
+        for n in resolution {
+            bytes.extend(n.to_le_bytes());
+        }
+        bytes.extend(1i32.to_le_bytes());
+        for x in min.iter().chain(&max) {
+            bytes.extend(x.to_le_bytes());
+        }
+        for d in data {
+            bytes.extend(d.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn load(test: &str, bytes: &[u8], scale: f64) -> Result<VoxelGrid, String> {
+        let path = std::env::temp_dir().join(format!(
+            "heterogeneous_medium_{}_{}.vol",
+            test,
+            std::process::id()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let grid = VoxelGrid::load(&path.to_string_lossy(), scale);
+        std::fs::remove_file(&path).unwrap();
+        grid
+    }
+
+    #[test]
+    fn load_vol_file() {
+        let bytes = vol([2, 1, 1], [0.0, 0.0, 0.0], [2.0, 1.0, 1.0], &[1.0, -3.0]);
+        let grid = load("ok", &bytes, 2.0).unwrap();
+        assert_eq!(grid.resolution, [2, 1, 1]);
+        assert!((grid.size - Vec3::new(2.0, 1.0, 1.0)).length() < 1e-12);
+        // scale 乘到每个体素上，负的密度截断为 0
+        assert_eq!(grid.data, vec![2.0, 0.0]);
+        assert_eq!(grid.max_density(), 2.0);
+
+        let Err(err) = load("magic", &bytes[1..], 1.0) else {
+            panic!("loaded a file without the VOL header");
+        };
+        assert!(err.ends_with("not a .vol file"), "{}", err);
+        let Err(err) = load("short", &bytes[..bytes.len() - 4], 1.0) else {
+            panic!("loaded a file with missing voxels");
+        };
+        assert!(
+            err.ends_with("expected 2 voxels, found 4 bytes of data"),
+            "{}",
+            err
+        );
+        let empty = vol([1, 1, 1], [0.0, 0.0, 0.0], [1.0, 0.0, 1.0], &[1.0]);
+        assert!(load("empty", &empty, 1.0).is_err());
+    }
+
+    #[test]
+    fn density_at_centres_and_edges() {
+        let grid = VoxelGrid::new(
+            [2, 1, 1],
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 1.0, 1.0),
+            vec![1.0, 3.0],
+        );
+        let at = |x: f64| grid.density(Point3::new(x, 0.5, 0.5));
+        // 体素中心是体素的值，两个中心之间线性插值
+        assert!((at(0.5) - 1.0).abs() < 1e-12);
+        assert!((at(1.5) - 3.0).abs() < 1e-12);
+        assert!((at(1.0) - 2.0).abs() < 1e-12);
+        assert!((at(0.75) - 1.5).abs() < 1e-12);
+        // 中心到网格边缘的半个体素取最近的值，网格外为 0
+        assert!((at(0.0) - 1.0).abs() < 1e-12);
+        assert!((at(2.0) - 3.0).abs() < 1e-12);
+        assert!((grid.density(Point3::new(1.5, 0.0, 1.0)) - 3.0).abs() < 1e-12);
+        assert_eq!(at(-0.01), 0.0);
+        assert_eq!(at(2.01), 0.0);
+        assert_eq!(grid.density(Point3::new(1.0, 1.5, 0.5)), 0.0);
+
+        // 2×2×2 的网格中心是八个体素的平均值
+        let data: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let grid = VoxelGrid::new(
+            [2, 2, 2],
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            data,
+        );
+        assert!((grid.density(Point3::new(0.0, 0.0, 0.0)) - 3.5).abs() < 1e-12);
+        assert!((grid.density(Point3::new(0.5, -0.5, -0.5)) - 1.0).abs() < 1e-12);
+        assert!((grid.density(Point3::new(-0.5, 0.5, 0.5)) - 6.0).abs() < 1e-12);
+    }
+
+    // 上界比实际密度大的常数场，让 ratio tracking 的每一步都乘上一个非零的比例
+    struct Loose {
+        density: f64,
+        majorant: f64,
+    }
+
+    impl DensityField for Loose {
+        fn density(&self, _p: Point3) -> f64 {
+            self.density
+        }
+
+        fn max_density(&self) -> f64 {
+            self.majorant
+        }
+    }
+
+    // 常数密度场的 ratio tracking 平均值等于 ConstantMedium 的解析透过率 exp(-σd)
+    #[test]
+    fn ratio_tracking_matches_constant_medium() {
+        let sigma = 0.8;
+        let boundary: Arc<dyn Hittable + Send + Sync> = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let white = Color::new(1.0, 1.0, 1.0);
+        let constant = ConstantMedium::new_with_color(Arc::clone(&boundary), sigma, white);
+        let grid = VoxelGrid::new(
+            [1, 1, 1],
+            Point3::new(-2.0, -2.0, -2.0),
+            Point3::new(2.0, 2.0, 2.0),
+            vec![sigma as f32],
+        );
+        let loose = Loose {
+            density: sigma,
+            majorant: 3.0 * sigma,
+        };
+
+        // 方向不是单位向量时距离也要按长度换算
+        let r = Ray::new(Point3::new(0.0, 0.3, -3.0), Vec3::new(0.0, 0.0, 2.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = constant.transmittance(&r, &ray_t).x();
+        let d = 2.0 * (1.0f64 - 0.09).sqrt();
+        assert!((expected - (-sigma * d).exp()).abs() < 1e-12);
+
+        let fields: [Arc<dyn DensityField + Send + Sync>; 2] = [Arc::new(grid), Arc::new(loose)];
+        for field in fields {
+            let medium = HeterogeneousMedium::new_with_phase(
+                Arc::clone(&boundary),
+                field,
+                Arc::new(Isotropic::new_with_color(white)),
+            );
+            let n = 200_000;
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let tr = medium.transmittance(&r, &ray_t);
+                assert_eq!(tr.x(), tr.z());
+                sum += tr.x();
+            }
+            let mean = sum / n as f64;
+            assert!((mean - expected).abs() < 0.005, "{} vs {}", mean, expected);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::easy_task::aabb::Aabb;
+use crate::easy_task::color::Color;
 use crate::easy_task::interval::Interval;
 use crate::easy_task::material::Material;
 use crate::easy_task::onb::Onb;
@@ -34,6 +35,17 @@ pub trait Hittable {
 
     fn bounding_box(&self) -> &Aabb;
 
+    // 阴影光线的求交：参与介质不挡光，只找第一个实体表面，介质的衰减由 transmittance 给出。
+    // 包含其他物体的容器要把这两个方法都转发给子物体
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        self.hit(r, ray_t, rec)
+    }
+
+    // 光线在 ray_t 这一段上穿过参与介质后剩下的比例，实体表面不影响
+    fn transmittance(&self, _r: &Ray, _ray_t: &Interval) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
     fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
         0.0
     }
@@ -177,7 +189,7 @@ pub struct Translate {
 
 impl Hittable for Translate {
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        let offset_r = Ray::new_time(r.origin() - self.offset, r.direction(), r.time());
+        let offset_r = self.to_object(r);
 
         if !self.object.hit(&offset_r, ray_t, rec) {
             return false;
@@ -188,12 +200,28 @@ impl Hittable for Translate {
         true
     }
 
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        if !self.object.shadow_hit(&self.to_object(r), ray_t, rec) {
+            return false;
+        }
+        rec.p += self.offset;
+        true
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        self.object.transmittance(&self.to_object(r), ray_t)
+    }
+
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
 }
 
 impl Translate {
+    fn to_object(&self, r: &Ray) -> Ray {
+        Ray::new_time(r.origin() - self.offset, r.direction(), r.time())
+    }
+
     pub fn new(object: Arc<dyn Hittable + Sync + Send>, offset: Vec3) -> Self {
         let bbox = object.bounding_box() + offset;
         Self {
@@ -214,7 +242,34 @@ pub struct RotateY {
 
 impl Hittable for RotateY {
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        // 将光线从世界空间变换到对象空间
+        // 在对象空间中确定是否存在交点（如果有，确定在哪里）
+        if !self.object.hit(&self.to_object(r), ray_t, rec) {
+            return false;
+        }
+        self.to_world(rec);
+        true
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        if !self.object.shadow_hit(&self.to_object(r), ray_t, rec) {
+            return false;
+        }
+        self.to_world(rec);
+        true
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        self.object.transmittance(&self.to_object(r), ray_t)
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+}
+
+impl RotateY {
+    // 将光线从世界空间变换到对象空间
+    fn to_object(&self, r: &Ray) -> Ray {
         let origin = Point3::new(
             self.cos_theta * r.origin().x() - self.sin_theta * r.origin().z(),
             r.origin().y(),
@@ -226,34 +281,22 @@ impl Hittable for RotateY {
             self.sin_theta * r.direction().x() + self.cos_theta * r.direction().z(),
         );
 
-        let rotated_r = Ray::new_time(origin, direction, r.time());
-
-        // 在对象空间中确定是否存在交点（如果有，确定在哪里）
-        if !self.object.hit(&rotated_r, ray_t, rec) {
-            return false;
-        }
+        Ray::new_time(origin, direction, r.time())
+    }
 
-        // 将交点从对象空间变换到世界空间
+    // 将交点和法线从对象空间变换到世界空间
+    fn to_world(&self, rec: &mut HitRecord) {
         let mut p = rec.p;
         p[0] = self.cos_theta * rec.p[0] + self.sin_theta * rec.p[2];
         p[2] = -self.sin_theta * rec.p[0] + self.cos_theta * rec.p[2];
         rec.p = p;
 
-        // 将法线从对象空间变换到世界空间
         let mut normal = rec.normal;
         normal[0] = self.cos_theta * rec.normal[0] + self.sin_theta * rec.normal[2];
         normal[2] = -self.sin_theta * rec.normal[0] + self.cos_theta * rec.normal[2];
         rec.normal = normal;
-
-        true
     }
 
-    fn bounding_box(&self) -> &Aabb {
-        &self.bbox
-    }
-}
-
-impl RotateY {
     pub fn new(p: Arc<dyn Hittable + Sync + Send>, angle: f64) -> Self {
         let radians = degrees_to_radians(angle);
         let sin_theta = radians.sin();
@@ -1,6 +1,7 @@
 use super::hittable::{HitRecord, Hittable};
 use super::ray::Ray;
 use crate::easy_task::aabb::Aabb;
+use crate::easy_task::color::Color;
 use crate::easy_task::interval::Interval;
 use crate::easy_task::rtweekend::random_int;
 use crate::easy_task::vec3::{Point3, Vec3};
@@ -49,6 +50,28 @@ impl Hittable for HittableList {
         hit_anything
     }
 
+    fn shadow_hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+
+        for object in self.objects.iter() {
+            if object.shadow_hit(r, &Interval::new(ray_t.min, closest_so_far), rec) {
+                hit_anything = true;
+                closest_so_far = rec.t;
+            }
+        }
+
+        hit_anything
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+        let mut tr = Color::new(1.0, 1.0, 1.0);
+        for object in self.objects.iter() {
+            tr = tr * object.transmittance(r, ray_t);
+        }
+        tr
+    }
+
     fn bounding_box(&self) -> &Aabb {
         &self.bbox
     }
@@ -0,0 +1,552 @@
+use crate::easy_task::deflate::{crc32, crc32_update, zlib_compress};
+use crate::easy_task::framebuffer::Framebuffer;
+use crate::easy_task::tone_mapping::DisplayTransform;
+use std::fs::{File, create_dir_all};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// 把帧缓冲编码成某种图像文件格式
+pub trait ImageWriter {
+    // 写出这种格式时文件应使用的扩展名
+    fn extension(&self) -> &'static str;
+    // 高动态范围格式直接保存线性辐射度，不做截断和 gamma 编码
+    fn high_dynamic_range(&self) -> bool {
+        false
+    }
+    // 低动态范围格式先用 display 做曝光、色调映射和 sRGB 编码
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()>;
+}
+
+// ASCII 格式的 PPM（P3），体积大但可以直接用文本编辑器查看
+pub struct PpmAsciiWriter;
+
+impl ImageWriter for PpmAsciiWriter {
+    fn extension(&self) -> &'static str {
+        "ppm"
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "P3")?;
+        writeln!(out, "{} {}", framebuffer.width(), framebuffer.height())?;
+        writeln!(out, "255")?;
+        for row in rgb8_rows(framebuffer, display) {
+            for pixel in row.chunks(3) {
+                write!(out, "{} {} {} ", pixel[0], pixel[1], pixel[2])?;
+            }
+            writeln!(out)?; // 在每行的像素数据之后插入换行符
+        }
+        Ok(())
+    }
+}
+
+// 二进制 PPM（P6）
+pub struct PpmBinaryWriter;
+
+impl ImageWriter for PpmBinaryWriter {
+    fn extension(&self) -> &'static str {
+        "ppm"
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        write!(
+            out,
+            "P6\n{} {}\n255\n",
+            framebuffer.width(),
+            framebuffer.height()
+        )?;
+        out.write_all(&rgb8_rows(framebuffer, display).concat())
+    }
+}
+
+// 8 位 RGB 的 PNG，每行自适应选择滤波器后用 zlib 压缩
+pub struct PngWriter;
+
+impl PngWriter {
+    fn chunk(out: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        out.write_all(kind)?;
+        out.write_all(data)?;
+        out.write_all(&crc32_update(crc32(kind), data).to_be_bytes())
+    }
+
+    // 对一行应用某种滤波器，prev 是上一行（第一行时为全 0）
+    fn filter(kind: u8, row: &[u8], prev: &[u8], out: &mut Vec<u8>) {
+        const BPP: usize = 3;
+        out.push(kind);
+        for x in 0..row.len() {
+            let a = if x >= BPP { row[x - BPP] } else { 0 };
+            let b = prev[x];
+            let c = if x >= BPP { prev[x - BPP] } else { 0 };
+            let predictor = match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                _ => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = (
+                        (p - a as i16).abs(),
+                        (p - b as i16).abs(),
+                        (p - c as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+            };
+            out.push(row[x].wrapping_sub(predictor));
+        }
+    }
+}
+
+impl ImageWriter for PngWriter {
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::new();
+        header.extend((framebuffer.width() as u32).to_be_bytes());
+        header.extend((framebuffer.height() as u32).to_be_bytes());
+        // 位深 8，颜色类型 2（RGB），默认压缩/滤波方式，不隔行
+        header.extend([8, 2, 0, 0, 0]);
+        Self::chunk(out, b"IHDR", &header)?;
+        // 像素值已经编码到 sRGB 空间
+        Self::chunk(out, b"sRGB", &[0])?;
+
+        // 每行取滤波后字节（按有符号数）绝对值之和最小的滤波器
+        let rows = rgb8_rows(framebuffer, display);
+        let zero = vec![0; framebuffer.width() * 3];
+        let mut filtered = Vec::with_capacity(rows.len() * (zero.len() + 1));
+        let mut candidate = Vec::with_capacity(zero.len() + 1);
+        for (j, row) in rows.iter().enumerate() {
+            let prev = if j > 0 { &rows[j - 1] } else { &zero };
+            let mut best: Option<(u64, Vec<u8>)> = None;
+            for kind in 0..5 {
+                candidate.clear();
+                Self::filter(kind, row, prev, &mut candidate);
+                let cost = candidate[1..]
+                    .iter()
+                    .map(|&v| (v as i8).unsigned_abs() as u64)
+                    .sum();
+                if best.as_ref().is_none_or(|(c, _)| cost < *c) {
+                    best = Some((cost, candidate.clone()));
+                }
+            }
+            filtered.extend(best.unwrap().1);
+        }
+        Self::chunk(out, b"IDAT", &zlib_compress(&filtered))?;
+        Self::chunk(out, b"IEND", &[])
+    }
+}
+
+// NaN 当作 0，其余值原样保留
+fn linear_pixel(framebuffer: &Framebuffer, i: usize, j: usize) -> [f32; 3] {
+    framebuffer
+        .get(i, j)
+        .e
+        .map(|c| if c.is_nan() { 0.0 } else { c as f32 })
+}
+
+// Radiance RGBE（.hdr），扫描线使用新式游程编码
+pub struct HdrWriter;
+
+impl HdrWriter {
+    // 三个通道共用一个指数：尾数按最大分量归一化到 [0.5, 1) 后乘 256
+    fn rgbe(rgb: [f32; 3]) -> [u8; 4] {
+        let [r, g, b] = rgb.map(|c| if c.is_nan() { 0.0 } else { c.max(0.0) });
+        let v = r.max(g).max(b).min(f32::MAX);
+        if v < 1e-32 {
+            return [0; 4];
+        }
+        let mut exponent = v.log2().floor() as i32 + 1;
+        if v / 2f32.powi(exponent) >= 1.0 {
+            exponent += 1;
+        }
+        // 指数字节最大表示 2^127，更亮的值（包括无穷大）饱和成能表示的最亮颜色
+        let exponent = exponent.min(127);
+        let scale = 256.0 / 2f32.powi(exponent);
+        let channel = |c: f32| (c * scale).min(255.0) as u8;
+        [channel(r), channel(g), channel(b), (exponent + 128) as u8]
+    }
+
+    // 单个通道的游程编码：长度至少为 4 的重复段写成 (128 + n, 值)，其余按原样分段写出
+    fn encode_channel(values: &[u8], out: &mut Vec<u8>) {
+        const MIN_RUN: usize = 4;
+        let mut pos = 0;
+        while pos < values.len() {
+            // 找到下一个足够长的重复段的起点
+            let mut run_start = pos;
+            let mut run_len = 0;
+            while run_start < values.len() {
+                run_len = values[run_start..]
+                    .iter()
+                    .take(127)
+                    .take_while(|&&v| v == values[run_start])
+                    .count();
+                if run_len >= MIN_RUN {
+                    break;
+                }
+                run_start += 1;
+            }
+            let literal_end = run_start.min(values.len());
+            for chunk in values[pos..literal_end].chunks(128) {
+                out.push(chunk.len() as u8);
+                out.extend_from_slice(chunk);
+            }
+            if run_start < values.len() {
+                out.push(128 + run_len as u8);
+                out.push(values[run_start]);
+                pos = run_start + run_len;
+            } else {
+                pos = values.len();
+            }
+        }
+    }
+}
+
+impl ImageWriter for HdrWriter {
+    fn extension(&self) -> &'static str {
+        "hdr"
+    }
+
+    fn high_dynamic_range(&self) -> bool {
+        true
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        _display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        write!(
+            out,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )?;
+        let mut line = Vec::with_capacity(width * 4);
+        for j in 0..height {
+            let pixels: Vec<[u8; 4]> = (0..width)
+                .map(|i| Self::rgbe(linear_pixel(framebuffer, i, j)))
+                .collect();
+            line.clear();
+            // 游程编码只支持 8 到 32767 的宽度，其它宽度写成平铺的像素
+            if (8..32768).contains(&width) {
+                line.extend([2, 2, (width >> 8) as u8, width as u8]);
+                for c in 0..4 {
+                    let channel: Vec<u8> = pixels.iter().map(|p| p[c]).collect();
+                    Self::encode_channel(&channel, &mut line);
+                }
+            } else {
+                line.extend(pixels.concat());
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+// PFM：小端 32 位浮点，扫描线从下往上存放
+pub struct PfmWriter;
+
+impl ImageWriter for PfmWriter {
+    fn extension(&self) -> &'static str {
+        "pfm"
+    }
+
+    fn high_dynamic_range(&self) -> bool {
+        true
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        _display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        // 比例因子为负表示小端
+        write!(
+            out,
+            "PF\n{} {}\n-1.0\n",
+            framebuffer.width(),
+            framebuffer.height()
+        )?;
+        let mut line = Vec::with_capacity(framebuffer.width() * 12);
+        for j in (0..framebuffer.height()).rev() {
+            line.clear();
+            for i in 0..framebuffer.width() {
+                for c in linear_pixel(framebuffer, i, j) {
+                    line.extend(c.to_le_bytes());
+                }
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+// OpenEXR 扫描线文件，R、G、B 三个 32 位浮点通道，可选不压缩或 ZIP 压缩
+pub struct ExrWriter {
+    pub zip: bool,
+}
+
+impl ExrWriter {
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+        header.extend(name.as_bytes());
+        header.push(0);
+        header.extend(kind.as_bytes());
+        header.push(0);
+        header.extend((value.len() as i32).to_le_bytes());
+        header.extend(value);
+    }
+
+    // ZIP 压缩前的预处理：先把奇偶字节分开，再对相邻字节做差分
+    fn zip_block(raw: &[u8]) -> Vec<u8> {
+        let half = raw.len().div_ceil(2);
+        let mut reordered = vec![0; raw.len()];
+        for (k, &byte) in raw.iter().enumerate() {
+            let index = if k % 2 == 0 { k / 2 } else { half + k / 2 };
+            reordered[index] = byte;
+        }
+        for k in (1..reordered.len()).rev() {
+            reordered[k] = reordered[k]
+                .wrapping_sub(reordered[k - 1])
+                .wrapping_add(128);
+        }
+        zlib_compress(&reordered)
+    }
+}
+
+impl ImageWriter for ExrWriter {
+    fn extension(&self) -> &'static str {
+        "exr"
+    }
+
+    fn high_dynamic_range(&self) -> bool {
+        true
+    }
+
+    fn write(
+        &self,
+        framebuffer: &Framebuffer,
+        _display: &DisplayTransform,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        // ZIP 压缩每 16 行一块，不压缩时每行一块
+        let lines_per_block = if self.zip { 16 } else { 1 };
+
+        let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        // 通道按名字排序；类型 2 表示 32 位浮点
+        let mut channels = Vec::new();
+        for name in ["B", "G", "R"] {
+            channels.extend(name.as_bytes());
+            channels.push(0);
+            channels.extend(2i32.to_le_bytes());
+            channels.extend([0, 0, 0, 0]);
+            channels.extend(1i32.to_le_bytes());
+            channels.extend(1i32.to_le_bytes());
+        }
+        channels.push(0);
+        Self::attribute(&mut header, "channels", "chlist", &channels);
+        Self::attribute(
+            &mut header,
+            "compression",
+            "compression",
+            &[if self.zip { 3 } else { 0 }],
+        );
+        let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        Self::attribute(&mut header, "dataWindow", "box2i", &window);
+        Self::attribute(&mut header, "displayWindow", "box2i", &window);
+        Self::attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        Self::attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        Self::attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        Self::attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+
+        // 每块的数据：对块内每一行，依次写出 B、G、R 通道的全部像素
+        let mut blocks = Vec::new();
+        for y0 in (0..height).step_by(lines_per_block) {
+            let mut raw = Vec::new();
+            for j in y0..(y0 + lines_per_block).min(height) {
+                let pixels: Vec<[f32; 3]> = (0..width)
+                    .map(|i| linear_pixel(framebuffer, i, j))
+                    .collect();
+                for c in [2, 1, 0] {
+                    for p in &pixels {
+                        raw.extend(p[c].to_le_bytes());
+                    }
+                }
+            }
+            // 压缩后反而更大时按原样存放，读取方据此判断
+            if self.zip {
+                let compressed = Self::zip_block(&raw);
+                if compressed.len() < raw.len() {
+                    raw = compressed;
+                }
+            }
+            blocks.push((y0 as i32, raw));
+        }
+
+        // 头部之后是每块在文件中的偏移表
+        out.write_all(&header)?;
+        let mut offset = (header.len() + blocks.len() * 8) as u64;
+        for (_, data) in &blocks {
+            out.write_all(&offset.to_le_bytes())?;
+            offset += 8 + data.len() as u64;
+        }
+        for (y, data) in &blocks {
+            out.write_all(&y.to_le_bytes())?;
+            out.write_all(&(data.len() as i32).to_le_bytes())?;
+            out.write_all(data)?;
+        }
+        Ok(())
+    }
+}
+
+fn rgb8_rows(framebuffer: &Framebuffer, display: &DisplayTransform) -> Vec<Vec<u8>> {
+    display
+        .apply(framebuffer)
+        .chunks(framebuffer.width().max(1))
+        .map(|row| row.concat())
+        .collect()
+}
+
+// 可选的输出格式名
+pub const FORMATS: [&str; 7] = [
+    "png",
+    "ppm",
+    "ppm-ascii",
+    "hdr",
+    "pfm",
+    "exr",
+    "exr-uncompressed",
+];
+
+pub fn writer_for_format(format: &str) -> Option<Box<dyn ImageWriter>> {
+    match format {
+        "png" => Some(Box::new(PngWriter)),
+        "ppm" => Some(Box::new(PpmBinaryWriter)),
+        "ppm-ascii" => Some(Box::new(PpmAsciiWriter)),
+        "hdr" => Some(Box::new(HdrWriter)),
+        "pfm" => Some(Box::new(PfmWriter)),
+        "exr" => Some(Box::new(ExrWriter { zip: true })),
+        "exr-uncompressed" => Some(Box::new(ExrWriter { zip: false })),
+        _ => None,
+    }
+}
+
+// 根据文件扩展名推断格式，.ppm 默认写成二进制，.exr 默认使用 ZIP 压缩
+pub fn format_for_path(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path)
+        .extension()?
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("png"),
+        "ppm" | "pnm" => Some("ppm"),
+        "hdr" => Some("hdr"),
+        "pfm" => Some("pfm"),
+        "exr" => Some("exr"),
+        _ => None,
+    }
+}
+
+// format 为 None 时由扩展名决定格式
+pub fn save_image(
+    path: &str,
+    format: Option<&str>,
+    framebuffer: &Framebuffer,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    let format = format.or_else(|| format_for_path(path)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot infer the image format from \"{}\"", path),
+        )
+    })?;
+    let writer = writer_for_format(format).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format \"{}\"", format),
+        )
+    })?;
+
+    if let Some(dir_path) = Path::new(path).parent() {
+        if !dir_path.as_os_str().is_empty() && !dir_path.exists() {
+            create_dir_all(dir_path)?;
+        }
+    }
+    let mut file = BufWriter::new(File::create(path)?);
+    writer.write(framebuffer, display, &mut file)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbe_encodes_shared_exponent() {
+        assert_eq!(HdrWriter::rgbe([1.0, 0.5, 0.0]), [128, 64, 0, 129]);
+        assert_eq!(HdrWriter::rgbe([0.0, 0.0, 0.0]), [0; 4]);
+        assert_eq!(HdrWriter::rgbe([-1.0, 0.0, 0.0]), [0; 4]);
+    }
+
+    #[test]
+    fn rgbe_saturates_instead_of_wrapping() {
+        assert_eq!(HdrWriter::rgbe([f32::INFINITY, 0.0, 0.0]), [255, 0, 0, 255]);
+        assert_eq!(
+            HdrWriter::rgbe([f32::MAX, f32::MAX, 1.0]),
+            [255, 255, 0, 255]
+        );
+        assert_eq!(HdrWriter::rgbe([f32::NAN, 1.0, 0.0]), [0, 128, 0, 129]);
+        assert_eq!(HdrWriter::rgbe([f32::NAN; 3]), [0; 4]);
+        // 2^126 刚好还能精确表示
+        assert_eq!(
+            HdrWriter::rgbe([2f32.powi(126), 0.0, 0.0]),
+            [128, 0, 0, 255]
+        );
+    }
+}
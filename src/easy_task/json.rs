@@ -0,0 +1,344 @@
+use std::fmt;
+
+// 带行列号的 JSON 值，场景加载器据此报告错误位置
+#[derive(Debug, Clone)]
+pub struct Json {
+    pub line: usize,
+    pub column: usize,
+    pub kind: JsonKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum JsonKind {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // 保留键的书写顺序和位置
+    Object(Vec<(JsonKey, Json)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonKey {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Json {
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            JsonKind::Null => "null",
+            JsonKind::Bool(_) => "a boolean",
+            JsonKind::Number(_) => "a number",
+            JsonKind::String(_) => "a string",
+            JsonKind::Array(_) => "an array",
+            JsonKind::Object(_) => "an object",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        JsonError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of file", expected))),
+        }
+    }
+
+    // 除了空白，还允许 // 行注释，方便在场景文件里写说明
+    fn skip_whitespace(&mut self) -> Result<(), JsonError> {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' {
+                self.bump();
+                if self.peek() != Some('/') {
+                    return Err(self.error("unexpected '/'"));
+                }
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace()?;
+        let (line, column) = (self.line, self.column);
+        let kind = match self.peek() {
+            Some('{') => self.object()?,
+            Some('[') => self.array()?,
+            Some('"') => JsonKind::String(self.string()?),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number()?,
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                    self.bump();
+                }
+                match word.as_str() {
+                    "true" => JsonKind::Bool(true),
+                    "false" => JsonKind::Bool(false),
+                    "null" => JsonKind::Null,
+                    _ => {
+                        return Err(JsonError {
+                            line,
+                            column,
+                            message: format!("unexpected word \"{}\"", word),
+                        });
+                    }
+                }
+            }
+            Some(c) => return Err(self.error(format!("unexpected character '{}'", c))),
+            None => return Err(self.error("unexpected end of file")),
+        };
+        Ok(Json { line, column, kind })
+    }
+
+    fn object(&mut self) -> Result<JsonKind, JsonError> {
+        self.expect('{')?;
+        let mut entries: Vec<(JsonKey, Json)> = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(JsonKind::Object(entries));
+        }
+        loop {
+            self.skip_whitespace()?;
+            let (line, column) = (self.line, self.column);
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a quoted key"));
+            }
+            let name = self.string()?;
+            if entries.iter().any(|(k, _)| k.name == name) {
+                return Err(JsonError {
+                    line,
+                    column,
+                    message: format!("duplicate key \"{}\"", name),
+                });
+            }
+            self.skip_whitespace()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            entries.push((JsonKey { name, line, column }, value));
+
+            self.skip_whitespace()?;
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some('}') => {
+                    self.bump();
+                    return Ok(JsonKind::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonKind, JsonError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(JsonKind::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace()?;
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {
+                    self.bump();
+                    return Ok(JsonKind::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            let digit = self
+                                .bump()
+                                .and_then(|c| c.to_digit(16))
+                                .ok_or_else(|| self.error("invalid \\u escape"))?;
+                            code = code * 16 + digit;
+                        }
+                        out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonKind, JsonError> {
+        let (line, column) = (self.line, self.column);
+        let mut text = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            text.push(c);
+            self.bump();
+        }
+        text.parse().map(JsonKind::Number).map_err(|_| JsonError {
+            line,
+            column,
+            message: format!("invalid number \"{}\"", text),
+        })
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("trailing characters after the top-level value"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(text: &str) -> (usize, usize, String) {
+        let e = parse(text).unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn values_keep_their_position() {
+        let json = parse("// 注释\n{\n  \"a\": [1, -2.5e1, true],\n  \"b\": null\n}").unwrap();
+        assert_eq!((json.line, json.column), (2, 1));
+        let JsonKind::Object(entries) = &json.kind else {
+            panic!("expected an object");
+        };
+        assert_eq!(entries[0].0.name, "a");
+        assert_eq!((entries[0].0.line, entries[0].0.column), (3, 3));
+        let JsonKind::Array(items) = &entries[0].1.kind else {
+            panic!("expected an array");
+        };
+        assert!(matches!(items[1].kind, JsonKind::Number(n) if n == -25.0));
+        assert!(matches!(items[2].kind, JsonKind::Bool(true)));
+        assert_eq!((items[2].line, items[2].column), (3, 20));
+        assert!(matches!(entries[1].1.kind, JsonKind::Null));
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        assert_eq!(
+            error_at("{\n  \"a\": 1\n  \"b\": 2\n}"),
+            (3, 3, String::from("expected ',' or '}'"))
+        );
+        assert_eq!(
+            error_at("[1,\n  tru]"),
+            (2, 3, String::from("unexpected word \"tru\""))
+        );
+        assert_eq!(
+            error_at("{\"a\": 1, \"a\": 2}"),
+            (1, 10, String::from("duplicate key \"a\""))
+        );
+        assert_eq!(
+            error_at("\"abc"),
+            (1, 5, String::from("unterminated string"))
+        );
+        assert_eq!(
+            error_at("[1, 2] 3"),
+            (
+                1,
+                8,
+                String::from("trailing characters after the top-level value")
+            )
+        );
+        assert_eq!(
+            error_at("[1.2.3]"),
+            (1, 2, String::from("invalid number \"1.2.3\""))
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        let json = parse(r#""a\"b\\c\u00e9\n""#).unwrap();
+        assert!(matches!(json.kind, JsonKind::String(ref s) if s == "a\"b\\cé\n"));
+    }
+}
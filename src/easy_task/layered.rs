@@ -0,0 +1,494 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::hittable::HitRecord;
+use crate::easy_task::material::{Material, NonePdf, ScatterRecord};
+use crate::easy_task::microfacet::{MicrofacetReflectionPdf, TrowbridgeReitz, to_local};
+use crate::easy_task::onb::Onb;
+use crate::easy_task::pdf::{MixturePdf, Pdf};
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rough_dielectric::fresnel_dielectric;
+use crate::easy_task::rtweekend::random_double;
+use crate::easy_task::spectral::{self, SampledWavelengths};
+use crate::easy_task::texture::Texture;
+use crate::easy_task::thin_film::ThinFilm;
+use crate::easy_task::vec3::{Point3, Vec3, dot, reflect, unit_vector};
+use std::sync::Arc;
+
+// 两个分量按 weights 组合成一个散射记录，返回被选中的分量，None 表示光线被吸收。
+// 选中镜面分量时直接沿用它的光线；选中非镜面分量时在所有非镜面分量的混合分布上采样，
+// 这时 eval 要除以 non_specular_weight，抵消只有这么大概率走到这一支
+fn combine(
+    weights: [f64; 2],
+    mut components: Vec<Option<ScatterRecord>>,
+    srec: &mut ScatterRecord,
+) -> Option<usize> {
+    let chosen = if random_double() < weights[0] { 0 } else { 1 };
+    let picked = components[chosen].as_ref()?;
+    if picked.skip_pdf {
+        srec.attenuation = picked.attenuation;
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray = picked.skip_pdf_ray;
+        srec.dispersive = picked.dispersive;
+        srec.sampled = picked.sampled;
+        return Some(chosen);
+    }
+
+    srec.pdf =
+        non_specular_pdf(weights, &mut components).expect("the picked component is not specular");
+    srec.attenuation = Color::new(1.0, 1.0, 1.0);
+    srec.skip_pdf = false;
+    srec.dispersive = components.iter().flatten().any(|s| s.dispersive);
+    srec.sampled = components.iter().flatten().any(|s| s.sampled);
+    srec.components = components;
+    Some(chosen)
+}
+
+// 所有非镜面分量的 pdf 按权重归一化后的混合分布，没有非镜面分量时返回 None。
+// pdf 从分量的记录中移出，子材质的 eval 只用到记录中的其他字段
+fn non_specular_pdf(
+    weights: [f64; 2],
+    components: &mut [Option<ScatterRecord>],
+) -> Option<Box<dyn Pdf>> {
+    let mut pdfs: Vec<(f64, Box<dyn Pdf>)> = Vec::new();
+    for (weight, component) in weights.iter().zip(components.iter_mut()) {
+        if let Some(s) = component.as_mut().filter(|s| !s.skip_pdf) {
+            pdfs.push((*weight, std::mem::replace(&mut s.pdf, Box::new(NonePdf))));
+        }
+    }
+    match (pdfs.pop(), pdfs.pop()) {
+        (Some((w1, p1)), Some((w0, p0))) => {
+            Some(Box::new(MixturePdf::weighted(p0, p1, w1 / (w0 + w1))))
+        }
+        (Some((_, p)), None) => Some(p),
+        _ => None,
+    }
+}
+
+// 与 combine 选中非镜面分量时建立的混合分布相同的密度
+fn mixture_pdf(
+    weights: [f64; 2],
+    mut components: Vec<Option<ScatterRecord>>,
+    scattered: &Ray,
+) -> f64 {
+    non_specular_pdf(weights, &mut components).map_or(0.0, |pdf| pdf.value(scattered.direction()))
+}
+
+// 组合后的结果是各个波长上的值时，仍是 RGB 的部分也要先展开到这些波长上再相加、相乘
+fn match_sampled(value: Color, value_sampled: bool, sampled: bool) -> Color {
+    if sampled && !value_sampled {
+        spectral::reflectance(value)
+    } else {
+        value
+    }
+}
+
+fn non_specular_weight(weights: [f64; 2], components: &[Option<ScatterRecord>]) -> f64 {
+    weights
+        .iter()
+        .zip(components)
+        .filter(|(_, s)| s.as_ref().is_some_and(|s| !s.skip_pdf))
+        .map(|(w, _)| w)
+        .sum()
+}
+
+fn scatter_component(
+    material: &Arc<dyn Material + Send + Sync>,
+    weight: f64,
+    r_in: &Ray,
+    rec: &HitRecord,
+) -> Option<ScatterRecord> {
+    if weight <= 0.0 {
+        return None;
+    }
+    let mut srec = ScatterRecord::default();
+    material.scatter(r_in, rec, &mut srec).then_some(srec)
+}
+
+// 按遮罩在两个材质之间混合，遮罩取纹理的第一个分量：0 完全是 a，1 完全是 b
+pub struct MixMaterial {
+    a: Arc<dyn Material + Send + Sync>,
+    b: Arc<dyn Material + Send + Sync>,
+    mask: Arc<dyn Texture + Send + Sync>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        a: Arc<dyn Material + Send + Sync>,
+        b: Arc<dyn Material + Send + Sync>,
+        mask: Arc<dyn Texture + Send + Sync>,
+    ) -> Self {
+        Self { a, b, mask }
+    }
+
+    fn weights(&self, u: f64, v: f64, p: Point3) -> [f64; 2] {
+        let t = self.mask.value(u, v, p).x().clamp(0.0, 1.0);
+        [1.0 - t, t]
+    }
+
+    fn components(
+        &self,
+        weights: [f64; 2],
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Vec<Option<ScatterRecord>> {
+        vec![
+            scatter_component(&self.a, weights[0], r_in, rec),
+            scatter_component(&self.b, weights[1], r_in, rec),
+        ]
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let weights = self.weights(rec.u, rec.v, rec.p);
+        combine(weights, self.components(weights, r_in, rec), srec).is_some()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: Point3) -> Color {
+        let [wa, wb] = self.weights(u, v, p);
+        wa * self.a.emitted(r_in, rec, u, v, p) + wb * self.b.emitted(r_in, rec, u, v, p)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let weights = self.weights(rec.u, rec.v, rec.p);
+        mixture_pdf(weights, self.components(weights, r_in, rec), scattered)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let weights = self.weights(rec.u, rec.v, rec.p);
+        let total = non_specular_weight(weights, &srec.components);
+        let mut f = Color::default();
+        for ((material, weight), component) in [&self.a, &self.b]
+            .into_iter()
+            .zip(weights)
+            .zip(&srec.components)
+        {
+            if let Some(s) = component.as_ref().filter(|s| !s.skip_pdf) {
+                let eval = material.eval(r_in, rec, s, scattered);
+                f += weight / total * match_sampled(eval, s.sampled, srec.sampled);
+            }
+        }
+        f
+    }
+}
+
+// 在任意材质上覆盖一层透明清漆：清漆表面按菲涅尔反射，透进去的光照到底层材质，
+// 进出清漆时各损失一次菲涅尔反射并被清漆的颜色吸收。
+// 忽略了清漆内部的折射和多次反射，底层材质直接使用外部的入射和出射方向
+pub struct CoatedMaterial {
+    base: Arc<dyn Material + Send + Sync>,
+    refraction_index: f64,
+    roughness: f64,
+    distribution: TrowbridgeReitz,
+    tint: Color,            // 垂直穿过清漆一个来回后剩下的颜色
+    film: Option<ThinFilm>, // 清漆表面的薄膜，反射率随波长变化
+}
+
+impl CoatedMaterial {
+    pub fn new(
+        base: Arc<dyn Material + Send + Sync>,
+        refraction_index: f64,
+        roughness: f64,
+    ) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Self {
+            base,
+            refraction_index,
+            roughness,
+            distribution: TrowbridgeReitz::new(roughness),
+            tint: Color::new(1.0, 1.0, 1.0),
+            film: None,
+        }
+    }
+
+    pub fn set_tint(&mut self, tint: Color) {
+        self.tint = tint;
+    }
+
+    pub fn set_film(&mut self, film: ThinFilm) {
+        self.film = Some(film);
+    }
+
+    // 清漆表面的反射率，有薄膜时各通道不同；给出 wavelengths 时是这几个波长上的值
+    fn fresnel(&self, rec: &HitRecord, cos: f64, wavelengths: Option<SampledWavelengths>) -> Color {
+        match &self.film {
+            Some(film) => film.reflectance(rec, cos, self.refraction_index, wavelengths),
+            None => {
+                let f = fresnel_dielectric(cos, self.refraction_index);
+                Color::new(f, f, f)
+            }
+        }
+    }
+
+    // 光谱模式下薄膜的反射率直接按路径的波长求出，清漆给出的结果也就是光谱值
+    fn film_sampled(&self) -> bool {
+        self.film.is_some() && spectral::current().is_some()
+    }
+
+    // 斜着穿过清漆的路程更长，吸收按 1/cosθ 增加；cos_i 不为正时光线没有再穿出清漆
+    fn transmittance(&self, cos_o: f64, cos_i: f64) -> Color {
+        let mut exponent = 0.5 / cos_o.max(1e-4);
+        if cos_i > 0.0 {
+            exponent += 0.5 / cos_i.max(1e-4);
+        }
+        Color::new(
+            self.tint.x().powf(exponent),
+            self.tint.y().powf(exponent),
+            self.tint.z().powf(exponent),
+        )
+    }
+
+    // 穿过清漆到达底层再出来的比例，不含选中底层的概率。
+    // entry 是入射一侧的透射率 1 - F(wo) 除以选中底层的概率，没有薄膜时为 1。
+    // sampled 时结果是各个波长上的值
+    fn base_scale(
+        &self,
+        rec: &HitRecord,
+        entry: Color,
+        cos_o: f64,
+        cos_i: f64,
+        sampled: bool,
+    ) -> Color {
+        let exit = if cos_i > 0.0 {
+            Color::new(1.0, 1.0, 1.0) - self.fresnel(rec, cos_i, spectral::current())
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        };
+        // 没有薄膜时菲涅尔项是灰色，展开前后相同
+        let transmittance = match_sampled(self.transmittance(cos_o, cos_i), false, sampled);
+        entry * exit * transmittance
+    }
+
+    // 粗糙清漆表面的 f·cosθi
+    fn coat_eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let uvw = Onb::new_from_w(rec.normal);
+        let wo = to_local(&uvw, wo);
+        let wi = to_local(&uvw, wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+        let wm = unit_vector(wo + wi);
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+        d * g / (4.0 * wo.z()) * self.fresnel(rec, dot(wo, wm), spectral::current())
+    }
+
+    // 清漆层和底层被选中的概率按 wo 方向的平均反射率分配，
+    // 同时返回反射率 F(wo) 和透射率 1 - F(wo) 分别除以对应概率后的颜色
+    fn weights(&self, rec: &HitRecord, cos_o: f64) -> ([f64; 2], [Color; 2]) {
+        let f = self.fresnel(rec, cos_o, spectral::current());
+        let p = (f.x() + f.y() + f.z()) / 3.0;
+        let t = Color::new(1.0, 1.0, 1.0) - f;
+        let scale = |c: Color, p: f64| if p > 0.0 { c / p } else { Color::default() };
+        ([p, 1.0 - p], [scale(f, p), scale(t, 1.0 - p)])
+    }
+
+    // 清漆和底层各自的散射记录，同时返回两者被选中的概率和入射一侧的 entry（见 base_scale）
+    fn components(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> ([f64; 2], Color, Vec<Option<ScatterRecord>>) {
+        let wo = -unit_vector(r_in.direction());
+        let (weights, [reflected, entry]) = self.weights(rec, dot(wo, rec.normal));
+        let coat = if self.roughness == 0.0 {
+            ScatterRecord {
+                attenuation: reflected,
+                skip_pdf: true,
+                skip_pdf_ray: Ray::new_time(rec.p, reflect(-wo, rec.normal), r_in.time()),
+                sampled: self.film_sampled(),
+                ..Default::default()
+            }
+        } else {
+            ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                pdf: Box::new(MicrofacetReflectionPdf::new(
+                    rec.normal,
+                    wo,
+                    self.distribution,
+                )),
+                sampled: self.film_sampled(),
+                ..Default::default()
+            }
+        };
+        let components = vec![
+            Some(coat),
+            scatter_component(&self.base, weights[1], r_in, rec),
+        ];
+        (weights, entry, components)
+    }
+}
+
+impl Material for CoatedMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let (weights, entry, components) = self.components(r_in, rec);
+        let Some(chosen) = combine(weights, components, srec) else {
+            return false;
+        };
+        // 底层是镜面时选中它的概率抵消了入射一侧的菲涅尔透射，剩下 entry
+        if srec.skip_pdf && chosen == 1 {
+            let cos_o = dot(-unit_vector(r_in.direction()), rec.normal);
+            let cos_i = dot(unit_vector(srec.skip_pdf_ray.direction()), rec.normal);
+            let sampled = srec.sampled || self.film_sampled();
+            srec.attenuation = match_sampled(srec.attenuation, srec.sampled, sampled)
+                * self.base_scale(rec, entry, cos_o, cos_i, sampled);
+            srec.sampled = sampled;
+        }
+        true
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: Point3) -> Color {
+        let cos_o = dot(-unit_vector(r_in.direction()), rec.normal);
+        // 发光由相机按光源光谱展开，这里用薄膜的 RGB 反射率
+        let transmitted = Color::new(1.0, 1.0, 1.0) - self.fresnel(rec, cos_o, None);
+        transmitted * self.transmittance(cos_o, 0.0) * self.base.emitted(r_in, rec, u, v, p)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (weights, _, components) = self.components(r_in, rec);
+        mixture_pdf(weights, components, scattered)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let wo = -unit_vector(r_in.direction());
+        let wi = unit_vector(scattered.direction());
+        let (cos_o, cos_i) = (dot(wo, rec.normal), dot(wi, rec.normal));
+        let (weights, [_, entry]) = self.weights(rec, cos_o);
+        let total = non_specular_weight(weights, &srec.components);
+        if total <= 0.0 {
+            return Color::default();
+        }
+        let mut f = Color::default();
+        if self.roughness > 0.0 {
+            f += match_sampled(
+                self.coat_eval(rec, wo, wi),
+                self.film_sampled(),
+                srec.sampled,
+            );
+        }
+        if let Some(s) = srec.components.get(1).and_then(|s| s.as_ref()) {
+            if !s.skip_pdf {
+                let eval = self.base.eval(r_in, rec, s, scattered);
+                f += weights[1]
+                    * self.base_scale(rec, entry, cos_o, cos_i, srec.sampled)
+                    * match_sampled(eval, s.sampled, srec.sampled);
+            }
+        }
+        f / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::conductor::Conductor;
+    use crate::easy_task::material::{Lambertian, sampling_integrals};
+    use crate::easy_task::texture::SolidColor;
+
+    fn rec() -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            front_face: true,
+            ..Default::default()
+        }
+    }
+
+    fn r_in() -> Ray {
+        Ray::new(Point3::new(-1.0, 1.0, 0.3), Vec3::new(1.0, -1.0, -0.3))
+    }
+
+    fn lambertian() -> Arc<dyn Material + Send + Sync> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.7)))
+    }
+
+    fn gold(roughness: f64) -> Arc<dyn Material + Send + Sync> {
+        Arc::new(Conductor::preset("gold", roughness).unwrap())
+    }
+
+    fn mix(a: Arc<dyn Material + Send + Sync>, b: Arc<dyn Material + Send + Sync>) -> MixMaterial {
+        let mask = Arc::new(SolidColor::new(Color::new(0.3, 0.3, 0.3)));
+        MixMaterial::new(a, b, mask)
+    }
+
+    // 按 srec.pdf 采样估计的 ∫ f·cosθ dω 与球面积分一致，说明混合分布的 generate 服从 value，
+    // eval 也按同一个混合分布归一化；scattering_pdf 在球面上积分为 1（所有分量都在上半球）
+    fn assert_sampling_follows_pdf(material: &dyn Material) {
+        let [estimate, integral, pdf] = sampling_integrals(material, &r_in(), &rec());
+        assert!((estimate - integral).abs() < 0.01, "{estimate} {integral}");
+        assert!(pdf <= 1.01 && pdf > 0.95, "{pdf}");
+    }
+
+    #[test]
+    fn mix_sampling_follows_pdf() {
+        assert_sampling_follows_pdf(&mix(lambertian(), gold(0.3)));
+        assert_sampling_follows_pdf(&mix(gold(0.2), gold(0.6)));
+
+        // 镜面分量不参与混合，剩下的漫反射分量的权重归一化为 1
+        let material = mix(lambertian(), gold(0.0));
+        assert_sampling_follows_pdf(&material);
+        let (r_in, rec) = (r_in(), rec());
+        let direction = Vec3::new(0.3, 0.8, -0.2);
+        let cos_pdf = dot(unit_vector(direction), rec.normal) / std::f64::consts::PI;
+        let pdf = material.scattering_pdf(&r_in, &rec, &Ray::new(rec.p, direction));
+        assert!((pdf - cos_pdf).abs() < 1e-12);
+
+        // 两个分量都是镜面时没有密度
+        let mirror = mix(gold(0.0), gold(0.0));
+        assert_eq!(
+            mirror.scattering_pdf(&r_in, &rec, &Ray::new(rec.p, direction)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn coated_sampling_follows_pdf() {
+        assert_sampling_follows_pdf(&CoatedMaterial::new(lambertian(), 1.5, 0.3));
+        assert_sampling_follows_pdf(&CoatedMaterial::new(gold(0.4), 1.5, 0.2));
+        assert_sampling_follows_pdf(&CoatedMaterial::new(lambertian(), 1.5, 0.0));
+        assert_sampling_follows_pdf(&CoatedMaterial::new(
+            Arc::new(mix(lambertian(), gold(0.2))),
+            1.5,
+            0.3,
+        ));
+    }
+
+    #[test]
+    fn film_values_stay_spectral_in_mixtures() {
+        let (r_in, rec) = (r_in(), rec());
+        let w = spectral::SampledWavelengths::sample();
+        spectral::set_current(Some(w));
+
+        let mut filmed = Conductor::preset("gold", 0.3).unwrap();
+        filmed.set_film(ThinFilm::new(350.0, 1.4));
+        let (a, b) = (
+            lambertian(),
+            Arc::new(filmed) as Arc<dyn Material + Send + Sync>,
+        );
+        let material = mix(a.clone(), b.clone());
+        let mut srec = ScatterRecord::default();
+        assert!(material.scatter(&r_in, &rec, &mut srec));
+        assert!(srec.sampled);
+
+        // 漫反射分量的 RGB 展开到路径的波长上，薄膜金属的分量原样相加
+        let scattered = Ray::new(rec.p, Vec3::new(0.2, 0.9, 0.1));
+        let [sa, sb] = [0, 1].map(|i| srec.components[i].as_ref().unwrap());
+        assert!(!sa.sampled && sb.sampled);
+        let expected = 0.7 * w.reflectance(a.eval(&r_in, &rec, sa, &scattered))
+            + 0.3 * b.eval(&r_in, &rec, sb, &scattered);
+        let f = material.eval(&r_in, &rec, &srec, &scattered);
+        assert!((f - expected).length() < 1e-12);
+
+        // 清漆上有薄膜时，不论选中哪一层，结果都是光谱值
+        let mut coated = CoatedMaterial::new(lambertian(), 1.5, 0.0);
+        coated.set_film(ThinFilm::new(350.0, 1.4));
+        for _ in 0..50 {
+            let mut srec = ScatterRecord::default();
+            assert!(coated.scatter(&r_in, &rec, &mut srec));
+            assert!(srec.sampled);
+        }
+
+        spectral::set_current(None);
+        let mut srec = ScatterRecord::default();
+        assert!(material.scatter(&r_in, &rec, &mut srec));
+        assert!(!srec.sampled);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::rtweekend::{INFINITY, degrees_to_radians};
+use crate::easy_task::vec3::{Point3, Vec3, dot, unit_vector};
+use std::fmt::Debug;
+
+// 对某个着色点采样光源的结果
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    pub direction: Vec3, // 从着色点指向光源的单位向量
+    pub distance: f64,   // 到光源的距离，平行光为无穷远
+    pub radiance: Color, // 到达着色点的入射辐射（已包含距离衰减）
+}
+
+// 点光源、聚光灯、平行光这类 δ 分布的光源：光线不可能恰好打中它们，
+// 所以不放进 lights 列表，而是在每个漫反射点上用阴影光线单独计算直接光照
+pub trait DeltaLight: Debug {
+    // 返回 None 表示这个点不受该光源照射（例如在聚光灯的光锥外）
+    fn sample_li(&self, p: Point3) -> Option<LightSample>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    position: Point3,
+    intensity: Color, // 辐射强度，距离为 1 处的入射辐射
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl DeltaLight for PointLight {
+    fn sample_li(&self, p: Point3) -> Option<LightSample> {
+        let offset = self.position - p;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: offset / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3, // 光锥的轴线方向
+    intensity: Color,
+    cos_falloff_start: f64, // 这个角度以内亮度不衰减
+    cos_total_width: f64,   // 这个角度以外完全没有光
+}
+
+impl SpotLight {
+    // 两个角度都是相对轴线的半角（度），在 falloff_start 和 total_width 之间平滑过渡到 0
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        total_width_degrees: f64,
+        falloff_start_degrees: f64,
+    ) -> Self {
+        let falloff_start = falloff_start_degrees.min(total_width_degrees);
+        Self {
+            position,
+            direction: unit_vector(direction),
+            intensity,
+            cos_falloff_start: degrees_to_radians(falloff_start).cos(),
+            cos_total_width: degrees_to_radians(total_width_degrees).cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total_width {
+            return 0.0;
+        }
+        let t =
+            (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl DeltaLight for SpotLight {
+    fn sample_li(&self, p: Point3) -> Option<LightSample> {
+        let offset = self.position - p;
+        let distance_squared = offset.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = offset / distance;
+        let falloff = self.falloff(dot(-direction, self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: falloff * self.intensity / distance_squared,
+        })
+    }
+}
+
+// 无穷远处的平行光，例如太阳
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    direction: Vec3,   // 光线传播的方向
+    irradiance: Color, // 垂直于光线的平面上的辐照度
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self {
+            direction: unit_vector(direction),
+            irradiance,
+        }
+    }
+}
+
+impl DeltaLight for DirectionalLight {
+    fn sample_li(&self, _p: Point3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
@@ -1,8 +1,11 @@
 use super::color::Color;
 use super::hittable::HitRecord;
 use super::ray::Ray;
-use crate::easy_task::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::easy_task::pdf::{
+    CosinePdf, HenyeyGreensteinPdf, MixturePdf, Pdf, SpherePdf, henyey_greenstein,
+};
 use crate::easy_task::rtweekend::{PI, random_double};
+use crate::easy_task::spectral::{Dispersion, hero_wavelength};
 use crate::easy_task::texture::{SolidColor, Texture};
 use crate::easy_task::vec3::{
     Point3, Vec3, dot, random_in_unit_disk, reflect, refract, unit_vector,
@@ -21,6 +24,18 @@ pub trait Material {
     fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
         0.0
     }
+
+    // BSDF 乘以余弦项 f·|cosθ|，即沿 scattered 方向的入射光对出射光的贡献系数。
+    // 默认由 attenuation 和 scattering_pdf 组成，适用于 attenuation 与方向无关的材质
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        srec.attenuation * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    // 物体内部介质的吸收系数 σa。光线穿过表面进入物体后，积分器按 Beer-Lambert 定律
+    // 对它在物体内走过的每一段衰减，直到从同一种材质的表面穿出
+    fn absorption(&self) -> Option<Color> {
+        None
+    }
 }
 
 pub struct Lambertian {
@@ -85,12 +100,22 @@ impl Material for Metal {
 
 pub struct Dielectric {
     pub refraction_index: f64,
+    dispersion: Option<Dispersion>, // 光谱模式下按波长计算折射率
 }
 
 impl Dielectric {
     #[allow(dead_code)]
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            dispersion: None,
+        }
+    }
+
+    // RGB 模式下使用 d 线处的折射率
+    pub fn set_dispersion(&mut self, dispersion: Dispersion) {
+        self.refraction_index = dispersion.nominal_index();
+        self.dispersion = Some(dispersion);
     }
 
     fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -105,10 +130,17 @@ impl Material for Dielectric {
         srec.attenuation = Color::new(1.0, 1.0, 1.0);
         srec.skip_pdf = true;
 
+        let refraction_index = match (self.dispersion, hero_wavelength()) {
+            (Some(dispersion), Some(lambda)) => {
+                srec.dispersive = true;
+                dispersion.refraction_index(lambda)
+            }
+            _ => self.refraction_index,
+        };
         let ri = if rec.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
         let unit_direction = unit_vector(r_in.direction());
         let cos_theta = dot(-unit_direction, rec.normal).min(1.0);
@@ -126,6 +158,18 @@ impl Material for Dielectric {
     }
 }
 
+// 不改变光线的界面，光线原样穿过。用作肥皂泡这类悬空薄膜下面的“空”底层
+pub struct Transparent;
+
+impl Material for Transparent {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray = Ray::new_time(rec.p, r_in.direction(), r_in.time());
+        true
+    }
+}
+
 #[derive(Clone)]
 pub struct DiffuseLight {
     tex: Arc<dyn Texture + Send + Sync>,
@@ -188,6 +232,60 @@ impl Material for Isotropic {
     }
 }
 
+// Henyey-Greenstein 相函数的参与介质散射，可以是两个波瓣的混合：
+// 以 weight 的概率按 g2 散射，其余按 g1，常用一个前向和一个后向的波瓣模拟云雾
+pub struct HenyeyGreenstein {
+    tex: Arc<dyn Texture + Sync + Send>,
+    g: [f64; 2],
+    weight: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(a: Arc<dyn Texture + Sync + Send>, g: f64) -> Self {
+        Self::new_double(a, g, g, 0.0)
+    }
+
+    pub fn new_double(a: Arc<dyn Texture + Sync + Send>, g1: f64, g2: f64, weight: f64) -> Self {
+        // |g| 接近 1 时相函数退化成 δ 分布
+        let clamp = |g: f64| g.clamp(-0.99, 0.99);
+        Self {
+            tex: a,
+            g: [clamp(g1), clamp(g2)],
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+
+    fn phase(&self, cos_theta: f64) -> f64 {
+        (1.0 - self.weight) * henyey_greenstein(cos_theta, self.g[0])
+            + self.weight * henyey_greenstein(cos_theta, self.g[1])
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let direction = unit_vector(r_in.direction());
+        srec.attenuation = self.tex.value(rec.u, rec.v, rec.p);
+        srec.pdf = if self.weight == 0.0 || self.g[0] == self.g[1] {
+            Box::new(HenyeyGreensteinPdf::new(direction, self.g[0]))
+        } else {
+            Box::new(MixturePdf::weighted(
+                Box::new(HenyeyGreensteinPdf::new(direction, self.g[0])),
+                Box::new(HenyeyGreensteinPdf::new(direction, self.g[1])),
+                self.weight,
+            ))
+        };
+        srec.skip_pdf = false;
+        true
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.phase(dot(
+            unit_vector(r_in.direction()),
+            unit_vector(scattered.direction()),
+        ))
+    }
+}
+
 pub struct NonePdf;
 
 impl Pdf for NonePdf {
@@ -204,6 +302,12 @@ pub struct ScatterRecord {
     pub pdf: Box<dyn Pdf>,
     pub skip_pdf: bool,
     pub skip_pdf_ray: Ray,
+    // 组合材质在 scatter 时记下各个子材质的散射记录，eval 时原样交还给子材质
+    pub components: Vec<Option<ScatterRecord>>,
+    // 散射方向取决于路径的主波长（色散），光谱模式下其余波长到此为止
+    pub dispersive: bool,
+    // attenuation 和 eval 给出的已经是当前路径各个波长上的值（例如薄膜的反射率），不再从 RGB 展开
+    pub sampled: bool,
 }
 
 impl Default for ScatterRecord {
@@ -213,6 +317,93 @@ impl Default for ScatterRecord {
             pdf: Box::new(NonePdf {}),
             skip_pdf: false,
             skip_pdf_ray: Ray::default(),
+            components: Vec::new(),
+            dispersive: false,
+            sampled: false,
+        }
+    }
+}
+
+// 测试用：取一次非镜面的散射记录，返回三个积分（颜色取三个通道的平均）：
+// 按 srec.pdf 采样估计的 ∫ f·cosθ dω、同一个积分在球面上直接求出的值、∫ scattering_pdf dω。
+// 前两个一致说明 generate 确实服从 value，第三个不能超过 1
+#[cfg(test)]
+pub fn sampling_integrals(material: &dyn Material, r_in: &Ray, rec: &HitRecord) -> [f64; 3] {
+    use crate::easy_task::pdf::integrate_sphere;
+
+    let mut srec = ScatterRecord::default();
+    for _ in 0..1000 {
+        srec = ScatterRecord::default();
+        assert!(material.scatter(r_in, rec, &mut srec));
+        if !srec.skip_pdf {
+            break;
+        }
+    }
+    assert!(!srec.skip_pdf, "no non-specular scatter");
+
+    let average = |c: Color| (c.x() + c.y() + c.z()) / 3.0;
+    let eval =
+        |direction: Vec3| average(material.eval(r_in, rec, &srec, &Ray::new(rec.p, direction)));
+    let samples = 200_000;
+    let mut estimate = 0.0;
+    for _ in 0..samples {
+        let direction = srec.pdf.generate();
+        let pdf = srec.pdf.value(direction);
+        if !direction.near_zero() && pdf > 0.0 {
+            estimate += eval(direction) / pdf;
+        }
+    }
+    [
+        estimate / samples as f64,
+        integrate_sphere(300_000, eval),
+        integrate_sphere(300_000, |direction| {
+            material.scattering_pdf(r_in, rec, &Ray::new(rec.p, direction))
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 双波瓣的密度是两个波瓣按权重的和，采样的分布也与之一致
+    #[test]
+    fn double_lobe_is_weighted_sum_of_lobes() {
+        let white = Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0)));
+        let (g1, g2, weight) = (0.8, -0.5, 0.3);
+        let phase = HenyeyGreenstein::new_double(white, g1, g2, weight);
+        let r_in = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.2, -1.0, 0.4));
+        let rec = HitRecord::default();
+        let mut srec = ScatterRecord::default();
+        assert!(phase.scatter(&r_in, &rec, &mut srec));
+
+        let forward = unit_vector(r_in.direction());
+        for direction in [
+            forward,
+            -forward,
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.3, 0.5, -0.8),
+        ] {
+            let cos = dot(forward, unit_vector(direction));
+            let expected =
+                (1.0 - weight) * henyey_greenstein(cos, g1) + weight * henyey_greenstein(cos, g2);
+            let scattered = Ray::new(rec.p, direction);
+            assert!((phase.scattering_pdf(&r_in, &rec, &scattered) - expected).abs() < 1e-12);
+            assert!((srec.pdf.value(direction) - expected).abs() < 1e-12);
         }
+
+        let [estimate, eval, pdf] = sampling_integrals(&phase, &r_in, &rec);
+        assert!((pdf - 1.0).abs() < 1e-3, "{}", pdf);
+        assert!((eval - 1.0).abs() < 1e-3, "{}", eval);
+        assert!((estimate - 1.0).abs() < 1e-9, "{}", estimate);
+
+        // 平均余弦也按权重混合
+        let n = 200_000;
+        let mean = (0..n)
+            .map(|_| dot(srec.pdf.generate(), forward))
+            .sum::<f64>()
+            / n as f64;
+        let g = (1.0 - weight) * g1 + weight * g2;
+        assert!((mean - g).abs() < 0.01, "{} vs {}", mean, g);
     }
 }
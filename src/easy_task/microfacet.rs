@@ -0,0 +1,150 @@
+use crate::easy_task::onb::Onb;
+use crate::easy_task::pdf::Pdf;
+use crate::easy_task::rtweekend::{PI, random_double};
+use crate::easy_task::vec3::{Vec3, cross, dot, reflect, unit_vector};
+
+// GGX（Trowbridge-Reitz）微表面法线分布，x、y 两个切线方向可以有不同的粗糙度。
+// 下面的方向都在局部坐标系中，宏观法线是 +z
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    // 粗糙度是感知上线性的参数，alpha = roughness²；太小的 alpha 会让分布在数值上退化
+    pub fn new(roughness: f64) -> Self {
+        let alpha = (roughness * roughness).max(1e-3);
+        Self {
+            alpha_x: alpha,
+            alpha_y: alpha,
+        }
+    }
+
+    // Disney 的各向异性参数化：anisotropic 从 0 到 1，高光沿 x 方向逐渐拉长
+    pub fn new_anisotropic(roughness: f64, anisotropic: f64) -> Self {
+        let aspect = (1.0 - 0.9 * anisotropic).sqrt();
+        let alpha = roughness * roughness;
+        Self {
+            alpha_x: (alpha / aspect).max(1e-3),
+            alpha_y: (alpha * aspect).max(1e-3),
+        }
+    }
+
+    // 法线分布函数 D(wm)
+    pub fn d(&self, wm: Vec3) -> f64 {
+        if wm.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x() / self.alpha_x;
+        let y = wm.y() / self.alpha_y;
+        let e = x * x + y * y + wm.z() * wm.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // Smith 遮蔽函数中的 Λ(w)
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        ((1.0 + (x * x + y * y) / cos2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // 入射和出射方向的联合遮蔽-阴影项（height-correlated 形式）
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // 从 wo 看过去可见的微表面法线的密度 D_wo(wm)
+    pub fn visible_pdf(&self, wo: Vec3, wm: Vec3) -> f64 {
+        let cos_o = wo.z().abs();
+        if cos_o <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * dot(wo, wm).abs() * self.d(wm) / cos_o
+    }
+
+    // 按可见法线分布采样微表面法线（Heitz 2018），wo 必须在上半球
+    pub fn sample_visible_normal(&self, wo: Vec3) -> Vec3 {
+        // 把视线方向拉伸到 alpha = 1 的半球配置
+        let vh = unit_vector(Vec3::new(
+            self.alpha_x * wo.x(),
+            self.alpha_y * wo.y(),
+            wo.z(),
+        ));
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(vh, t1);
+
+        // 在投影后的圆盘上均匀采样，再按可见部分压缩
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        unit_vector(Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+}
+
+// 世界坐标和以法线为 z 轴的局部坐标之间的转换
+pub fn to_local(uvw: &Onb, v: Vec3) -> Vec3 {
+    Vec3::new(dot(v, uvw.u()), dot(v, uvw.v()), dot(v, uvw.w()))
+}
+
+// 微表面反射的方向分布：先采样可见法线，再把 wo 绕它镜面反射
+pub struct MicrofacetReflectionPdf {
+    uvw: Onb,
+    wo: Vec3, // 局部坐标中指向观察者的方向
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetReflectionPdf {
+    pub fn new(normal: Vec3, wo: Vec3, distribution: TrowbridgeReitz) -> Self {
+        let uvw = Onb::new_from_w(normal);
+        let wo = to_local(&uvw, unit_vector(wo));
+        Self {
+            uvw,
+            wo,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for MicrofacetReflectionPdf {
+    // 反射方向的密度 = D_wo(wm) / (4 |wo·wm|)
+    fn value(&self, direction: Vec3) -> f64 {
+        let wi = to_local(&self.uvw, unit_vector(direction));
+        if wi.z() <= 0.0 || self.wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = unit_vector(wi + self.wo);
+        let cos_m = dot(self.wo, wm);
+        if cos_m <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.visible_pdf(self.wo, wm) / (4.0 * cos_m)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let wm = self.distribution.sample_visible_normal(self.wo);
+        let wi = reflect(-self.wo, wm);
+        self.uvw.transform(wi)
+    }
+}
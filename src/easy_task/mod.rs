@@ -2,17 +2,40 @@ pub mod aabb;
 pub mod bvh_node;
 pub mod camera;
 pub mod color;
+pub mod conductor;
 pub mod constant_medium;
+pub mod deflate;
+pub mod disney;
+pub mod distribution;
+pub mod environment;
+pub mod flat_bvh;
+pub mod framebuffer;
+pub mod heterogeneous_medium;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image_writer;
 pub mod interval;
+pub mod json;
+pub mod layered;
+pub mod light;
 pub mod material;
+pub mod microfacet;
+pub mod obj_loader;
 pub mod onb;
 mod pdf;
 pub mod perlin;
 pub mod quad;
 pub mod ray;
+pub mod rough_dielectric;
 pub mod rtw_image;
 pub mod rtweekend;
+pub mod scene;
+pub mod scheduler;
+pub mod sky;
+pub mod spectral;
+pub mod subsurface;
 pub mod texture;
+pub mod thin_film;
+pub mod tone_mapping;
+pub mod triangle;
 pub mod vec3;
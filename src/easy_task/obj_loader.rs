@@ -0,0 +1,560 @@
+use crate::easy_task::bvh_node::{BvhConfig, build_bvh};
+use crate::easy_task::color::Color;
+use crate::easy_task::hittable::Hittable;
+use crate::easy_task::hittable_list::HittableList;
+use crate::easy_task::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::easy_task::texture::ImageTexture;
+use crate::easy_task::triangle::{MeshFace, TriangleMesh};
+use crate::easy_task::vec3::{Point3, Vec3, cross, unit_vector};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// MTL 中的一个材质定义，只保留能映射到现有材质的字段
+#[derive(Debug, Clone)]
+struct MtlMaterial {
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: f64,
+    dissolve: f64,
+    illum: i32,
+    map_kd: Option<Arc<ImageTexture>>, // 读 MTL 时就加载，读不出来的贴图在对应的行报错
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            kd: Color::new(0.73, 0.73, 0.73),
+            ks: Color::default(),
+            ke: Color::default(),
+            ns: 0.0,
+            ni: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn build(&self) -> Arc<dyn Material + Send + Sync> {
+        let max = |c: Color| c.x().max(c.y()).max(c.z());
+
+        if max(self.ke) > 0.0 {
+            return Arc::new(DiffuseLight::new_color(self.ke));
+        }
+        // illum 4/6/7 是透明材质，d < 1 也按玻璃处理
+        if matches!(self.illum, 4 | 6 | 7) || self.dissolve < 1.0 {
+            return Arc::new(Dielectric::new(self.ni));
+        }
+        // illum 3 表示镜面反射，但 Ks 为黑色时按漫反射处理，否则会变成黑色的镜子
+        if max(self.ks) > 0.0 && (self.illum == 3 || max(self.ks) >= max(self.kd)) {
+            // Phong 指数越大越光滑，换算成 Metal 的 fuzz
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            return Arc::new(Metal::new(self.ks, fuzz));
+        }
+        match &self.map_kd {
+            Some(texture) => Arc::new(Lambertian::new_texture(texture.clone())),
+            None => Arc::new(Lambertian::new(self.kd)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError {
+            file: self.file.to_string(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn floats<const N: usize>(&self, args: &[&str], min: usize) -> Result<[f64; N], ObjError> {
+        if args.len() < min {
+            return Err(self.error(format!(
+                "expected at least {} numbers, found {}",
+                min,
+                args.len()
+            )));
+        }
+        let mut out = [0.0; N];
+        for (slot, arg) in out.iter_mut().zip(args) {
+            *slot = arg
+                .parse()
+                .map_err(|_| self.error(format!("invalid number \"{}\"", arg)))?;
+        }
+        Ok(out)
+    }
+
+    fn color(&self, args: &[&str]) -> Result<Color, ObjError> {
+        // "Kd 0.5" 这种只有一个分量的写法表示灰色
+        if args.len() == 1 {
+            let [g] = self.floats::<1>(args, 1)?;
+            return Ok(Color::new(g, g, g));
+        }
+        let [r, g, b] = self.floats::<3>(args, 3)?;
+        Ok(Color::new(r, g, b))
+    }
+
+    // OBJ 索引从 1 开始，负数表示相对当前末尾
+    fn index(&self, token: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+        let raw: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index \"{}\"", what, token)))?;
+        let resolved = if raw < 0 { count as i64 + raw } else { raw - 1 };
+        if raw == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(format!(
+                "{} index {} out of range (have {})",
+                what, raw, count
+            )));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+// 读取文件并按逻辑行切分，处理注释和 '\' 续行
+fn logical_lines(path: &Path) -> Result<Vec<(usize, String)>, ObjError> {
+    let text = std::fs::read_to_string(path).map_err(|e| ObjError {
+        file: path.display().to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    let mut start = 0;
+    for (i, raw) in text.lines().enumerate() {
+        let content = raw.split('#').next().unwrap_or("");
+        if pending.is_empty() {
+            start = i + 1;
+        }
+        if let Some(stripped) = content.trim_end().strip_suffix('\\') {
+            pending.push_str(stripped);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(content);
+        if !pending.trim().is_empty() {
+            lines.push((start, pending.trim().to_string()));
+        }
+        pending.clear();
+    }
+    if !pending.trim().is_empty() {
+        lines.push((start, pending.trim().to_string()));
+    }
+    Ok(lines)
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, MtlMaterial>) -> Result<(), ObjError> {
+    let file = path.display().to_string();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut parser = Parser {
+        file: &file,
+        line: 0,
+    };
+    let mut current: Option<String> = None;
+
+    for (line, text) in logical_lines(path)? {
+        parser.line = line;
+        let mut tokens = text.split_whitespace();
+        let keyword = tokens.next().unwrap_or("");
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(parser.error("newmtl without a name"));
+            }
+            materials.insert(name.clone(), MtlMaterial::default());
+            current = Some(name);
+            continue;
+        }
+
+        let Some(mat) = current.as_ref().and_then(|name| materials.get_mut(name)) else {
+            return Err(parser.error(format!("\"{}\" before any newmtl", keyword)));
+        };
+        match keyword {
+            "Kd" => mat.kd = parser.color(&args)?,
+            "Ks" => mat.ks = parser.color(&args)?,
+            "Ke" => mat.ke = parser.color(&args)?,
+            "Ns" => mat.ns = parser.floats::<1>(&args, 1)?[0],
+            "Ni" => mat.ni = parser.floats::<1>(&args, 1)?[0],
+            "d" => mat.dissolve = parser.floats::<1>(&args, 1)?[0],
+            "Tr" => mat.dissolve = 1.0 - parser.floats::<1>(&args, 1)?[0],
+            "illum" => {
+                mat.illum = args
+                    .first()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| parser.error("illum expects an integer"))?;
+            }
+            "map_Kd" => {
+                // 贴图选项（-s、-o 等）不支持，取最后一个参数作为文件名
+                let name = args
+                    .last()
+                    .ok_or_else(|| parser.error("map_Kd without a file name"))?;
+                let texture = dir.join(name);
+                let image = ImageTexture::try_new(&texture.to_string_lossy()).ok_or_else(|| {
+                    parser.error(format!("cannot load texture \"{}\"", texture.display()))
+                })?;
+                mat.map_kd = Some(Arc::new(image));
+            }
+            // 其余 MTL 语句（Ka、map_Bump 等）没有对应的材质参数，直接忽略
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// 同一分组、同一材质的面合成一个 TriangleMesh
+#[derive(Default)]
+struct FaceBatch {
+    faces: Vec<(MeshFace, u32)>,
+}
+
+// bvh 决定各个网格内部和整个模型的 BVH 怎样建立
+pub fn load_obj(path: &str, bvh: BvhConfig) -> Result<Arc<dyn Hittable + Send + Sync>, ObjError> {
+    let obj_path = Path::new(path);
+    let dir = obj_path.parent().unwrap_or(Path::new(""));
+    let mut parser = Parser {
+        file: path,
+        line: 0,
+    };
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+
+    // 批次按出现顺序保存，另用 (分组, 材质) 索引，每个面只需查一次表
+    let mut batches: Vec<((String, Option<String>), FaceBatch)> = Vec::new();
+    let mut batch_index: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group = String::from("default");
+    let mut material: Option<String> = None;
+    let mut smoothing = 0u32;
+
+    for (line, text) in logical_lines(obj_path)? {
+        parser.line = line;
+        let mut tokens = text.split_whitespace();
+        let keyword = tokens.next().unwrap_or("");
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parser.floats::<3>(&args, 3)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parser.floats::<2>(&args, 1)?;
+                uvs.push((u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parser.floats::<3>(&args, 3)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parser.error("face needs at least 3 vertices"));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let v = parser.index(parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                    let vt = match parts.next() {
+                        Some(s) if !s.is_empty() => Some(parser.index(s, uvs.len(), "texcoord")?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(s) if !s.is_empty() => {
+                            Some(parser.index(s, normals.len(), "normal")?)
+                        }
+                        _ => None,
+                    };
+                    corners.push((v, vt, vn));
+                }
+
+                let key = (group.clone(), material.clone());
+                let i = *batch_index.entry(key.clone()).or_insert_with(|| {
+                    batches.push((key, FaceBatch::default()));
+                    batches.len() - 1
+                });
+                let batch = &mut batches[i].1;
+
+                // 多边形按扇形三角化
+                for i in 1..corners.len() - 1 {
+                    let c = [corners[0], corners[i], corners[i + 1]];
+                    let face = MeshFace {
+                        vertices: [c[0].0, c[1].0, c[2].0],
+                        uvs: match (c[0].1, c[1].1, c[2].1) {
+                            (Some(a), Some(b), Some(d)) => Some([a, b, d]),
+                            _ => None,
+                        },
+                        normals: match (c[0].2, c[1].2, c[2].2) {
+                            (Some(a), Some(b), Some(d)) => Some([a, b, d]),
+                            _ => None,
+                        },
+                    };
+                    batch.faces.push((face, smoothing));
+                }
+            }
+            "g" | "o" => {
+                group = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+            }
+            "s" => {
+                smoothing = match args.first().copied() {
+                    None | Some("off") => 0,
+                    Some("on") => 1,
+                    Some(s) => s
+                        .parse()
+                        .map_err(|_| parser.error(format!("invalid smoothing group \"{}\"", s)))?,
+                };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(parser.error(format!("unknown material \"{}\"", name)));
+                }
+                material = Some(name);
+            }
+            "mtllib" => {
+                for name in &args {
+                    load_mtl(&dir.join(name), &mut materials)?;
+                }
+            }
+            // 曲线、曲面等其它语句不支持，忽略
+            _ => {}
+        }
+    }
+
+    let smooth_normals = smoothing_normals(&positions, &batches);
+
+    // 每个材质只构建一次（贴图只读一次），被所有用到它的批次共享
+    let mut built: HashMap<Option<String>, Arc<dyn Material + Send + Sync>> = HashMap::new();
+    let mut world = HittableList::default();
+    for ((_, mat_name), batch) in &batches {
+        let mat = built
+            .entry(mat_name.clone())
+            .or_insert_with(|| match mat_name {
+                Some(name) => materials[name].build(),
+                None => MtlMaterial::default().build(),
+            })
+            .clone();
+        // 全部由退化面组成的批次直接跳过
+        let mesh = build_mesh(&positions, &normals, &uvs, &smooth_normals, batch, mat, bvh);
+        if let Some(mesh) = mesh {
+            world.add(Arc::new(mesh));
+        }
+    }
+
+    if world.objects.is_empty() {
+        return Err(ObjError {
+            file: path.to_string(),
+            line: 0,
+            message: String::from("no faces found"),
+        });
+    }
+
+    Ok(build_bvh(&mut world, bvh))
+}
+
+// 没有 vn 但在平滑组里的面，按 (平滑组, 顶点) 累加面积加权的面法线
+fn smoothing_normals(
+    positions: &[Point3],
+    batches: &[((String, Option<String>), FaceBatch)],
+) -> HashMap<(u32, usize), Vec3> {
+    let mut accum: HashMap<(u32, usize), Vec3> = HashMap::new();
+    for (_, batch) in batches {
+        for (face, group) in &batch.faces {
+            if *group == 0 || face.normals.is_some() {
+                continue;
+            }
+            let [a, b, c] = face.vertices;
+            let n = cross(positions[b] - positions[a], positions[c] - positions[a]);
+            for v in face.vertices {
+                *accum.entry((*group, v)).or_default() += n;
+            }
+        }
+    }
+    accum
+        .into_iter()
+        .filter(|(_, n)| n.length_squared() > 0.0)
+        .map(|(k, n)| (k, unit_vector(n)))
+        .collect()
+}
+
+// 把全局缓冲区压缩成这个批次实际用到的部分
+fn compact<K: Copy + Eq + std::hash::Hash, T: Copy>(
+    map: &mut HashMap<K, usize>,
+    out: &mut Vec<T>,
+    keys: [K; 3],
+    value: impl Fn(K) -> T,
+) -> [usize; 3] {
+    keys.map(|key| {
+        *map.entry(key).or_insert_with(|| {
+            out.push(value(key));
+            out.len() - 1
+        })
+    })
+}
+
+fn build_mesh(
+    positions: &[Point3],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+    smooth_normals: &HashMap<(u32, usize), Vec3>,
+    batch: &FaceBatch,
+    mat: Arc<dyn Material + Send + Sync>,
+    bvh: BvhConfig,
+) -> Option<TriangleMesh> {
+    let mut out_positions = Vec::new();
+    let mut out_normals = Vec::new();
+    let mut out_uvs = Vec::new();
+    let mut position_map = HashMap::new();
+    let mut normal_map = HashMap::new();
+    let mut smooth_map = HashMap::new();
+    let mut uv_map = HashMap::new();
+
+    let mut faces = Vec::with_capacity(batch.faces.len());
+    for (face, group) in &batch.faces {
+        let mut out = MeshFace::new(compact(
+            &mut position_map,
+            &mut out_positions,
+            face.vertices,
+            |i| positions[i],
+        ));
+        out.uvs = face
+            .uvs
+            .map(|idx| compact(&mut uv_map, &mut out_uvs, idx, |i| uvs[i]));
+        out.normals = match face.normals {
+            Some(idx) => Some(compact(&mut normal_map, &mut out_normals, idx, |i| {
+                normals[i]
+            })),
+            None if *group != 0
+                && face
+                    .vertices
+                    .iter()
+                    .all(|v| smooth_normals.contains_key(&(*group, *v))) =>
+            {
+                // 生成的平滑法线和文件里的 vn 放在同一个缓冲区里，用不同的键去重
+                Some(compact(
+                    &mut smooth_map,
+                    &mut out_normals,
+                    face.vertices.map(|v| (*group, v)),
+                    |key| smooth_normals[&key],
+                ))
+            }
+            None => None,
+        };
+        faces.push(out);
+    }
+
+    TriangleMesh::try_new_with(out_positions, out_normals, out_uvs, faces, mat, bvh.split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::hittable::HitRecord;
+    use crate::easy_task::interval::Interval;
+    use crate::easy_task::material::ScatterRecord;
+    use crate::easy_task::ray::Ray;
+    use std::path::PathBuf;
+
+    // 把几个文件写进一个临时目录，返回其中 OBJ 文件的路径
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("obj_loader_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        dir.join(files[0].0)
+    }
+
+    // 从 z = 1 向下打到 (x, y, 0)，返回命中的法线和漫反射颜色
+    fn probe(world: &Arc<dyn Hittable + Send + Sync>, x: f64, y: f64) -> Option<(Vec3, Color)> {
+        let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, &Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return None;
+        }
+        let mut srec = ScatterRecord::default();
+        assert!(rec.mat.clone()?.scatter(&r, &rec, &mut srec));
+        Some((rec.normal, srec.attenuation))
+    }
+
+    #[test]
+    fn parses_faces_negative_indices_and_materials() {
+        let obj = "mtllib colors.mtl\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   usemtl red\n\
+                   f 1 2 3 4\n\
+                   v 2 0 0\nv 3 0 0\nv 2 1 0\n\
+                   usemtl blue\n\
+                   f -3 -2 -1\n";
+        let mtl = "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n";
+        let path = write_files("parse", &[("model.obj", obj), ("colors.mtl", mtl)]);
+        let world = load_obj(&path.to_string_lossy(), BvhConfig::default()).unwrap();
+
+        // 四边形按扇形拆成两个三角形，都用红色；负索引指向最后三个顶点
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        for (x, y, color) in [(0.7, 0.2, red), (0.2, 0.7, red), (2.2, 0.2, blue)] {
+            let (normal, albedo) = probe(&world, x, y).unwrap();
+            assert!((normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+            assert!((albedo - color).length() < 1e-12);
+        }
+        assert!(probe(&world, 1.5, 0.5).is_none());
+    }
+
+    #[test]
+    fn errors_name_the_offending_line() {
+        let obj = "v 0 0 0\nv 1 0 0\nf 1 2 -3\n";
+        let path = write_files("index", &[("model.obj", obj)]);
+        let Err(err) = load_obj(&path.to_string_lossy(), BvhConfig::default()) else {
+            panic!("out-of-range index accepted");
+        };
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("out of range"), "{}", err);
+
+        // 读不出来的贴图在 MTL 的对应行报错，而不是渲染成默认颜色
+        let obj = "mtllib broken.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl textured\nf 1 2 3\n";
+        let mtl = "newmtl textured\nKd 1 1 1\nmap_Kd missing.png\n";
+        let path = write_files("texture", &[("model.obj", obj), ("broken.mtl", mtl)]);
+        let Err(err) = load_obj(&path.to_string_lossy(), BvhConfig::default()) else {
+            panic!("missing texture accepted");
+        };
+        assert!(err.file.ends_with("broken.mtl"), "{}", err);
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("missing.png"), "{}", err);
+    }
+}
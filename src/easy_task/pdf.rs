@@ -8,6 +8,7 @@ use std::sync::Arc;
 
 pub trait Pdf {
     fn value(&self, direction: Vec3) -> f64;
+    // 返回零向量表示这次采样失败，路径在这里终止
     fn generate(&self) -> Vec3;
 }
 
@@ -23,6 +24,56 @@ impl Pdf for SpherePdf {
         vec3::random_unit_vector()
     }
 }
+
+// Henyey-Greenstein 相函数，cos_theta 是散射方向与原传播方向的夹角余弦。
+// g > 0 偏向前向散射，g < 0 偏向后向散射，g = 0 退化为各向同性
+pub fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * rtweekend::PI * denom * denom.max(0.0).sqrt())
+}
+
+// 按 Henyey-Greenstein 相函数采样散射方向
+pub struct HenyeyGreensteinPdf {
+    uvw: Onb, // w 是原传播方向
+    g: f64,
+}
+
+impl HenyeyGreensteinPdf {
+    pub fn new(direction: Vec3, g: f64) -> Self {
+        Self {
+            uvw: Onb::new_from_w(direction),
+            g,
+        }
+    }
+}
+
+impl Pdf for HenyeyGreensteinPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        henyey_greenstein(
+            vec3::dot(vec3::unit_vector(direction), self.uvw.w()),
+            self.g,
+        )
+    }
+
+    fn generate(&self) -> Vec3 {
+        let g = self.g;
+        let xi = rtweekend::random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * rtweekend::PI * rtweekend::random_double();
+        self.uvw.transform(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
 pub struct CosinePdf {
     uvw: Onb,
 }
@@ -54,6 +105,7 @@ pub struct HittablePdf {
 }
 
 impl HittablePdf {
+    #[allow(dead_code)]
     pub fn new(objects: Arc<dyn Hittable + Send + Sync>, origin: Point3) -> Self {
         Self { objects, origin }
     }
@@ -71,24 +123,86 @@ impl Pdf for HittablePdf {
 
 pub struct MixturePdf {
     pub p: [Box<dyn Pdf>; 2],
+    pub weight: f64, // 选中 p[1] 的概率
 }
 
 impl MixturePdf {
+    #[allow(dead_code)]
     pub fn new(p0: Box<dyn Pdf>, p1: Box<dyn Pdf>) -> Self {
-        Self { p: [p0, p1] }
+        Self::weighted(p0, p1, 0.5)
+    }
+
+    pub fn weighted(p0: Box<dyn Pdf>, p1: Box<dyn Pdf>, weight: f64) -> Self {
+        Self {
+            p: [p0, p1],
+            weight,
+        }
     }
 }
 
 impl Pdf for MixturePdf {
     fn value(&self, direction: Vec3) -> f64 {
-        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+        (1.0 - self.weight) * self.p[0].value(direction) + self.weight * self.p[1].value(direction)
     }
 
     fn generate(&self) -> Vec3 {
-        if rtweekend::random_double_range(0.0, 1.0) < 0.5 {
+        if rtweekend::random_double_range(0.0, 1.0) >= self.weight {
             self.p[0].generate()
         } else {
             self.p[1].generate()
         }
     }
 }
+
+// 用球面上的 Fibonacci 点集求 ∫ f dω，点的间距处处均匀，两极附近的窄波瓣也能分辨
+#[cfg(test)]
+pub fn integrate_sphere(n: usize, f: impl Fn(Vec3) -> f64) -> f64 {
+    let golden_angle = rtweekend::PI * (3.0 - 5f64.sqrt());
+    let mut sum = 0.0;
+    for k in 0..n {
+        let z = 1.0 - (2 * k + 1) as f64 / n as f64;
+        let r = (1.0 - z * z).sqrt();
+        let phi = golden_angle * k as f64;
+        sum += f(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+    }
+    sum * 4.0 * rtweekend::PI / n as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GS: [f64; 3] = [-0.8, 0.0, 0.8];
+
+    #[test]
+    fn henyey_greenstein_is_normalized() {
+        let axis = vec3::unit_vector(Vec3::new(1.0, 2.0, -0.5));
+        for g in GS {
+            let phase = integrate_sphere(300_000, |d| henyey_greenstein(vec3::dot(d, axis), g));
+            assert!((phase - 1.0).abs() < 1e-3, "g = {}: {}", g, phase);
+            let pdf = HenyeyGreensteinPdf::new(axis, g);
+            let value = integrate_sphere(300_000, |d| pdf.value(d));
+            assert!((value - 1.0).abs() < 1e-3, "g = {}: {}", g, value);
+        }
+        // g = 0 是各向同性的
+        assert!((henyey_greenstein(0.3, 0.0) - 1.0 / (4.0 * rtweekend::PI)).abs() < 1e-15);
+    }
+
+    // 相函数的平均余弦就是 g
+    #[test]
+    fn sampled_mean_cosine_is_g() {
+        let axis = vec3::unit_vector(Vec3::new(-0.3, 0.1, 1.0));
+        let n = 200_000;
+        for g in GS {
+            let pdf = HenyeyGreensteinPdf::new(axis, g);
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let d = pdf.generate();
+                assert!((d.length() - 1.0).abs() < 1e-9);
+                sum += vec3::dot(d, axis);
+            }
+            let mean = sum / n as f64;
+            assert!((mean - g).abs() < 0.01, "g = {}: {}", g, mean);
+        }
+    }
+}
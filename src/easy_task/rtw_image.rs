@@ -1,7 +1,24 @@
+use crate::easy_task::color::{Color, srgb_to_linear};
 use stb_image::image;
 pub const BYTES_PER_PIXEL: usize = 3;
 static MAGENTA: [u8; BYTES_PER_PIXEL] = [255, 0, 255];
 
+// 图像文件的候选路径，按查找顺序排列
+pub fn search_paths(filename: &str) -> Vec<String> {
+    let imagedir = std::env::var("RTW_IMAGES").unwrap_or_else(|_| String::from("images"));
+    let mut paths = Vec::new();
+    if !imagedir.is_empty() {
+        paths.push(format!("{}/{}", imagedir, filename));
+    }
+    paths.push(filename.to_string());
+    let mut prefix = String::new();
+    for _ in 0..7 {
+        paths.push(format!("{}images/{}", prefix, filename));
+        prefix += "../";
+    }
+    paths
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RtwImage {
     data: Vec<u8>,
@@ -13,38 +30,17 @@ pub struct RtwImage {
 impl RtwImage {
     #[allow(dead_code)]
     pub fn new(image_filename: &str) -> Self {
-        let filename = image_filename;
-        let imagedir = std::env::var("RTW_IMAGES").unwrap_or_else(|_| String::from("images"));
+        Self::try_new(image_filename)
+            .unwrap_or_else(|| panic!("ERROR: Could not load image file \"{}\".", image_filename))
+    }
 
+    // 按 RTW_IMAGES、当前目录和各级上层 images 目录依次查找，找不到返回 None
+    pub fn try_new(image_filename: &str) -> Option<Self> {
         let mut _self = Self::default();
-        if !imagedir.is_empty() && _self.load(&format!("{}/{}", imagedir, filename)) {
-            return _self;
-        }
-        if _self.load(filename) {
-            return _self;
-        }
-        if _self.load(&format!("images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../../images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../../../images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../../../../images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../../../../../images/{}", filename)) {
-            return _self;
-        }
-        if _self.load(&format!("../../../../../../images/{}", filename)) {
-            return _self;
-        }
-        panic!("ERROR: Could not load image file \"{}\".", filename);
+        search_paths(image_filename)
+            .iter()
+            .any(|path| _self.load(path))
+            .then_some(_self)
     }
 
     pub fn load(&mut self, filename: &str) -> bool {
@@ -103,3 +99,58 @@ impl RtwImage {
         high - 1
     }
 }
+
+// 浮点像素的图像，保存线性辐射度，用于环境光照
+#[derive(Debug, Clone, Default)]
+pub struct HdrImage {
+    data: Vec<f32>,
+    width: usize,
+    height: usize,
+}
+
+impl HdrImage {
+    // .hdr 等浮点格式直接使用；普通 8 位图像按 sRGB 解码成线性值
+    pub fn try_new(image_filename: &str) -> Option<Self> {
+        search_paths(image_filename)
+            .iter()
+            .find_map(
+                |path| match image::load_with_depth(path, BYTES_PER_PIXEL, false) {
+                    image::LoadResult::Error(_) => None,
+                    image::LoadResult::ImageU8(image) => Some(Self {
+                        data: image
+                            .data
+                            .iter()
+                            .map(|&b| srgb_to_linear(b as f64 / 255.0) as f32)
+                            .collect(),
+                        width: image.width,
+                        height: image.height,
+                    }),
+                    image::LoadResult::ImageF32(image) => Some(Self {
+                        data: image.data,
+                        width: image.width,
+                        height: image.height,
+                    }),
+                },
+            )
+            .filter(|image| image.width > 0 && image.height > 0)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        let offset = (y * self.width + x) * BYTES_PER_PIXEL;
+        Color::new(
+            self.data[offset] as f64,
+            self.data[offset + 1] as f64,
+            self.data[offset + 2] as f64,
+        )
+    }
+}
@@ -1,12 +1,21 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
 pub const INFINITY: f64 = f64::INFINITY;
 pub const PI: f64 = std::f64::consts::PI;
 
+thread_local! {
+    // 每个线程一个生成器，默认用系统熵初始化，需要可复现时用 seed_thread_rng 重新播种
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_os_rng());
+}
+
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
 pub fn random_double() -> f64 {
-    rand::random::<f64>()
+    RNG.with(|rng| rng.borrow_mut().random::<f64>())
 }
 
 pub fn random_double_range(min: f64, max: f64) -> f64 {
@@ -16,3 +25,15 @@ pub fn random_double_range(min: f64, max: f64) -> f64 {
 pub fn random_int(min: i32, max: i32) -> i32 {
     random_double_range(min as f64, (max + 1) as f64) as i32
 }
+
+pub fn seed_thread_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// 把基础种子和一个下标（行号、分块号等）混合成互不相关的种子
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed ^ index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
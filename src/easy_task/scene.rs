@@ -0,0 +1,1297 @@
+use crate::easy_task::bvh_node::{BvhConfig, BvhLayout, BvhSplit, build_bvh};
+use crate::easy_task::camera::{Camera, MisHeuristic};
+use crate::easy_task::conductor::{CONDUCTORS, Conductor};
+use crate::easy_task::constant_medium::ConstantMedium;
+use crate::easy_task::disney::Disney;
+use crate::easy_task::environment::{Environment, EnvironmentLight};
+use crate::easy_task::heterogeneous_medium::{
+    DensityField, HeterogeneousMedium, NoiseDensity, VoxelGrid,
+};
+use crate::easy_task::hittable::{Hittable, RotateY, Sphere, Translate};
+use crate::easy_task::hittable_list::HittableList;
+use crate::easy_task::json::{self, Json, JsonKey, JsonKind};
+use crate::easy_task::layered::{CoatedMaterial, MixMaterial};
+use crate::easy_task::light::{DeltaLight, DirectionalLight, PointLight, SpotLight};
+use crate::easy_task::material::{
+    Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal, Transparent,
+};
+use crate::easy_task::obj_loader::load_obj;
+use crate::easy_task::quad::{Quad, box_};
+use crate::easy_task::rough_dielectric::RoughDielectric;
+use crate::easy_task::rtw_image::HdrImage;
+use crate::easy_task::sky::PhysicalSky;
+use crate::easy_task::spectral::{Dispersion, GLASSES};
+use crate::easy_task::subsurface::Subsurface;
+use crate::easy_task::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::easy_task::thin_film::ThinFilm;
+use crate::easy_task::tone_mapping::ToneMapper;
+use crate::easy_task::triangle::Triangle;
+use crate::easy_task::vec3::Vec3;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type TextureRef = Arc<dyn Texture + Send + Sync>;
+type MaterialRef = Arc<dyn Material + Send + Sync>;
+type HittableRef = Arc<dyn Hittable + Send + Sync>;
+type EnvironmentRef = Arc<dyn Environment + Send + Sync>;
+type DeltaLightRef = Arc<dyn DeltaLight + Send + Sync>;
+type DensityFieldRef = Arc<dyn DensityField + Send + Sync>;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableRef,
+    pub lights: Option<HittableRef>, // 需要重要性采样的光源，None 表示只有 δ 光源或没有光源
+}
+
+impl Scene {
+    // 用环境光代替背景色，并把它加入光源列表参与重要性采样
+    pub fn set_environment<E>(&mut self, environment: Arc<E>)
+    where
+        E: Environment + Hittable + Send + Sync + 'static,
+    {
+        self.add_environment(environment.clone(), environment);
+    }
+
+    // 同一个环境光对象分别以 Environment 和 Hittable 的身份传入
+    fn add_environment(&mut self, environment: EnvironmentRef, light: HittableRef) {
+        self.camera.environment = Some(environment);
+        self.lights = Some(match self.lights.take() {
+            Some(existing) => {
+                let mut list = HittableList::default();
+                list.add(existing);
+                list.add(light);
+                Arc::new(list)
+            }
+            None => light,
+        });
+    }
+
+    fn with_environment(
+        camera: Camera,
+        world: HittableList,
+        lights: HittableList,
+        environment: Option<(EnvironmentRef, HittableRef)>,
+    ) -> Self {
+        let mut scene = Scene {
+            camera,
+            world: Arc::new(world),
+            lights: None,
+        };
+        if !lights.objects.is_empty() {
+            scene.lights = Some(Arc::new(lights));
+        }
+        if let Some((environment, light)) = environment {
+            scene.add_environment(environment, light);
+        }
+        scene
+    }
+
+    pub fn render(&mut self) {
+        self.camera
+            .render(Arc::clone(&self.world), self.lights.clone());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    // 出错字段的完整路径，例如 objects[2].material.albedo
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: ", self.file, self.line, self.column)?;
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// 一个 JSON 对象的读取器：记录哪些键被读过，最后把没用到的键当作未知字段报错
+struct Fields<'a> {
+    file: &'a str,
+    path: String,
+    entries: &'a [(JsonKey, Json)],
+    used: Vec<bool>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(file: &'a str, path: &str, value: &'a Json) -> Result<Self, SceneError> {
+        match &value.kind {
+            JsonKind::Object(entries) => Ok(Self {
+                file,
+                path: path.to_string(),
+                entries,
+                used: vec![false; entries.len()],
+            }),
+            _ => Err(error_at(
+                file,
+                value,
+                path,
+                format!("expected an object, found {}", value.type_name()),
+            )),
+        }
+    }
+
+    fn field(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&'a Json> {
+        let i = self.entries.iter().position(|(k, _)| k.name == key)?;
+        self.used[i] = true;
+        Some(&self.entries[i].1)
+    }
+
+    fn required(&mut self, key: &str, object: &Json) -> Result<&'a Json, SceneError> {
+        self.get(key).ok_or_else(|| {
+            error_at(
+                self.file,
+                object,
+                &self.field(key),
+                String::from("missing required field"),
+            )
+        })
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        for ((key, _), used) in self.entries.iter().zip(&self.used) {
+            if !used {
+                return Err(SceneError {
+                    file: self.file.to_string(),
+                    line: key.line,
+                    column: key.column,
+                    field: self.field(&key.name),
+                    message: String::from("unknown key"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error_at(file: &str, value: &Json, field: &str, message: String) -> SceneError {
+    SceneError {
+        file: file.to_string(),
+        line: value.line,
+        column: value.column,
+        field: field.to_string(),
+        message,
+    }
+}
+
+struct Loader {
+    file: String,
+    dir: PathBuf,
+    bvh: BvhConfig, // 场景文件里的 bvh 没有写 split 或 layout 时使用
+    textures: HashMap<String, TextureRef>,
+    materials: HashMap<String, MaterialRef>,
+    transforms: Cell<usize>, // 正在解析的 translate、rotate_y 的层数
+}
+
+impl Loader {
+    fn error(&self, value: &Json, field: &str, message: impl Into<String>) -> SceneError {
+        error_at(&self.file, value, field, message.into())
+    }
+
+    fn number(&self, value: &Json, field: &str) -> Result<f64, SceneError> {
+        match value.kind {
+            JsonKind::Number(n) => Ok(n),
+            _ => Err(self.error(
+                value,
+                field,
+                format!("expected a number, found {}", value.type_name()),
+            )),
+        }
+    }
+
+    fn positive(&self, value: &Json, field: &str) -> Result<f64, SceneError> {
+        let n = self.number(value, field)?;
+        if n > 0.0 {
+            Ok(n)
+        } else {
+            Err(self.error(value, field, format!("must be positive, got {}", n)))
+        }
+    }
+
+    fn integer(&self, value: &Json, field: &str) -> Result<i32, SceneError> {
+        let n = self.number(value, field)?;
+        if n.fract() != 0.0 || n < 1.0 || n > i32::MAX as f64 {
+            return Err(self.error(
+                value,
+                field,
+                format!("expected a positive integer, got {}", n),
+            ));
+        }
+        Ok(n as i32)
+    }
+
+    fn string<'v>(&self, value: &'v Json, field: &str) -> Result<&'v str, SceneError> {
+        match &value.kind {
+            JsonKind::String(s) => Ok(s),
+            _ => Err(self.error(
+                value,
+                field,
+                format!("expected a string, found {}", value.type_name()),
+            )),
+        }
+    }
+
+    fn array<'v>(&self, value: &'v Json, field: &str) -> Result<&'v [Json], SceneError> {
+        match &value.kind {
+            JsonKind::Array(items) => Ok(items),
+            _ => Err(self.error(
+                value,
+                field,
+                format!("expected an array, found {}", value.type_name()),
+            )),
+        }
+    }
+
+    fn vec3(&self, value: &Json, field: &str) -> Result<Vec3, SceneError> {
+        let items = self.array(value, field)?;
+        if items.len() != 3 {
+            return Err(self.error(
+                value,
+                field,
+                format!("expected 3 numbers, found {}", items.len()),
+            ));
+        }
+        let mut v = Vec3::default();
+        for (i, item) in items.iter().enumerate() {
+            v[i] = self.number(item, &format!("{}[{}]", field, i))?;
+        }
+        Ok(v)
+    }
+
+    fn kind<'v>(&self, fields: &mut Fields<'v>, object: &Json) -> Result<&'v str, SceneError> {
+        let field = fields.field("type");
+        let value = fields.required("type", object)?;
+        self.string(value, &field)
+    }
+
+    fn resolve(&self, name: &str) -> PathBuf {
+        let relative = self.dir.join(name);
+        if relative.exists() {
+            relative
+        } else {
+            PathBuf::from(name)
+        }
+    }
+
+    // 纹理可以写成颜色数组、已命名纹理的名字或内联对象
+    fn texture(&self, value: &Json, path: &str) -> Result<TextureRef, SceneError> {
+        match &value.kind {
+            JsonKind::Array(_) => Ok(Arc::new(SolidColor::new(self.vec3(value, path)?))),
+            JsonKind::String(name) => self
+                .textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| self.error(value, path, format!("unknown texture \"{}\"", name))),
+            _ => {
+                let mut fields = Fields::new(&self.file, path, value)?;
+                let kind = self.kind(&mut fields, value)?;
+                let tex: TextureRef = match kind {
+                    "solid" => {
+                        let color = fields.required("color", value)?;
+                        Arc::new(SolidColor::new(self.vec3(color, &fields.field("color"))?))
+                    }
+                    "checker" => {
+                        let scale = fields.required("scale", value)?;
+                        let even = fields.required("even", value)?;
+                        let odd = fields.required("odd", value)?;
+                        Arc::new(CheckerTexture::new(
+                            self.positive(scale, &fields.field("scale"))?,
+                            self.texture(even, &fields.field("even"))?,
+                            self.texture(odd, &fields.field("odd"))?,
+                        ))
+                    }
+                    "noise" => {
+                        let scale = fields.required("scale", value)?;
+                        Arc::new(NoiseTexture::new(
+                            self.number(scale, &fields.field("scale"))?,
+                        ))
+                    }
+                    "image" => {
+                        let field = fields.field("file");
+                        let file = fields.required("file", value)?;
+                        let name = self.string(file, &field)?;
+                        let image = ImageTexture::try_new(&self.resolve(name).to_string_lossy())
+                            .or_else(|| ImageTexture::try_new(name))
+                            .ok_or_else(|| {
+                                self.error(file, &field, format!("cannot load image \"{}\"", name))
+                            })?;
+                        Arc::new(image)
+                    }
+                    other => {
+                        return Err(self.error(
+                            value,
+                            &fields.field("type"),
+                            format!("unknown texture type \"{}\"", other),
+                        ));
+                    }
+                };
+                fields.finish()?;
+                Ok(tex)
+            }
+        }
+    }
+
+    // 色散可以写成预设玻璃的名字，或者 {"type": "cauchy", "a", "b"}、
+    // {"type": "sellmeier", "b": [3 个数], "c": [3 个数]}，波长以 μm 为单位
+    fn dispersion(&self, value: &Json, path: &str) -> Result<Dispersion, SceneError> {
+        if let JsonKind::String(name) = &value.kind {
+            return Dispersion::preset(name).ok_or_else(|| {
+                let names: Vec<&str> = GLASSES.iter().map(|(n, _)| *n).collect();
+                self.error(
+                    value,
+                    path,
+                    format!(
+                        "unknown glass \"{}\", expected one of {}",
+                        name,
+                        names.join(", ")
+                    ),
+                )
+            });
+        }
+
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let kind = self.kind(&mut fields, value)?;
+        let dispersion = match kind {
+            "cauchy" => {
+                let a = fields.required("a", value)?;
+                let a = self.positive(a, &fields.field("a"))?;
+                let b = fields.required("b", value)?;
+                let b = self.number(b, &fields.field("b"))?;
+                Dispersion::Cauchy { a, b }
+            }
+            "sellmeier" => {
+                let b = fields.required("b", value)?;
+                let b = self.vec3(b, &fields.field("b"))?;
+                let c = fields.required("c", value)?;
+                let c = self.vec3(c, &fields.field("c"))?;
+                Dispersion::Sellmeier {
+                    b: [b[0], b[1], b[2]],
+                    c: [c[0], c[1], c[2]],
+                }
+            }
+            other => {
+                return Err(self.error(
+                    value,
+                    &fields.field("type"),
+                    format!("unknown dispersion type \"{}\"", other),
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok(dispersion)
+    }
+
+    // 相函数的不对称参数 g，必须在 (-1, 1) 之间
+    fn asymmetry(&self, value: &Json, path: &str) -> Result<f64, SceneError> {
+        let g = self.number(value, path)?;
+        if g.abs() >= 1.0 {
+            return Err(self.error(value, path, format!("must be between -1 and 1, got {}", g)));
+        }
+        Ok(g)
+    }
+
+    // 0 到 1 之间的标量参数，可以直接写数字，也可以用纹理（取第一个分量）
+    fn scalar_texture(&self, value: &Json, path: &str) -> Result<TextureRef, SceneError> {
+        if let JsonKind::Number(n) = value.kind {
+            if !(0.0..=1.0).contains(&n) {
+                return Err(self.error(value, path, "must be between 0 and 1"));
+            }
+            return Ok(Arc::new(SolidColor::new(Vec3::new(n, n, n))));
+        }
+        self.texture(value, path)
+    }
+
+    // 薄膜的厚度（nm）可以是一个数，也可以是纹理，纹理的第一个分量在 thickness_range 内插值。
+    // 膜的折射率写在 ior_key 里，默认是水的 1.33
+    fn thin_film(
+        &self,
+        fields: &mut Fields,
+        object: &Json,
+        ior_key: &str,
+    ) -> Result<ThinFilm, SceneError> {
+        let ior = match fields.get(ior_key) {
+            Some(v) => self.positive(v, &fields.field(ior_key))?,
+            None => 1.33,
+        };
+        let thickness = fields.required("thickness", object)?;
+        if let JsonKind::Number(_) = thickness.kind {
+            let d = self.positive(thickness, &fields.field("thickness"))?;
+            return Ok(ThinFilm::new(d, ior));
+        }
+        let tex = self.scalar_texture(thickness, &fields.field("thickness"))?;
+        let field = fields.field("thickness_range");
+        let range = fields.get("thickness_range").ok_or_else(|| {
+            self.error(
+                thickness,
+                &field,
+                "required when the thickness is a texture",
+            )
+        })?;
+        let items = self.array(range, &field)?;
+        if items.len() != 2 {
+            return Err(self.error(
+                range,
+                &field,
+                format!("expected 2 numbers, found {}", items.len()),
+            ));
+        }
+        let min = self.number(&items[0], &format!("{}[0]", field))?;
+        let max = self.number(&items[1], &format!("{}[1]", field))?;
+        if !(0.0 <= min && min <= max) {
+            return Err(self.error(range, &field, "must satisfy 0 <= min <= max"));
+        }
+        Ok(ThinFilm::new_textured(tex, min, max, ior))
+    }
+
+    fn material(&self, value: &Json, path: &str) -> Result<MaterialRef, SceneError> {
+        if let JsonKind::String(name) = &value.kind {
+            return self
+                .materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| self.error(value, path, format!("unknown material \"{}\"", name)));
+        }
+
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let kind = self.kind(&mut fields, value)?;
+        let mat: MaterialRef = match kind {
+            "lambertian" => {
+                let albedo = fields.required("albedo", value)?;
+                Arc::new(Lambertian::new_texture(
+                    self.texture(albedo, &fields.field("albedo"))?,
+                ))
+            }
+            "metal" => {
+                let albedo = fields.required("albedo", value)?;
+                let fuzz = match fields.get("fuzz") {
+                    Some(v) => self.number(v, &fields.field("fuzz"))?,
+                    None => 0.0,
+                };
+                Arc::new(Metal::new(
+                    self.vec3(albedo, &fields.field("albedo"))?,
+                    fuzz,
+                ))
+            }
+            // 导体要么用 "metal" 指定预设，要么直接给出复折射率的 "eta" 和 "k"
+            "conductor" => {
+                let roughness = match fields.get("roughness") {
+                    Some(v) => {
+                        let field = fields.field("roughness");
+                        let r = self.number(v, &field)?;
+                        if !(0.0..=1.0).contains(&r) {
+                            return Err(self.error(v, &field, "must be between 0 and 1"));
+                        }
+                        r
+                    }
+                    None => 0.0,
+                };
+                let mut conductor = match fields.get("metal") {
+                    Some(v) => {
+                        let field = fields.field("metal");
+                        let name = self.string(v, &field)?;
+                        Conductor::preset(name, roughness).ok_or_else(|| {
+                            self.error(
+                                v,
+                                &field,
+                                format!(
+                                    "unknown metal \"{}\" (available: {})",
+                                    name,
+                                    CONDUCTORS.map(|(n, _, _)| n).join(", ")
+                                ),
+                            )
+                        })?
+                    }
+                    None => {
+                        let eta = fields.required("eta", value)?;
+                        let eta = self.vec3(eta, &fields.field("eta"))?;
+                        let k = fields.required("k", value)?;
+                        let k = self.vec3(k, &fields.field("k"))?;
+                        Conductor::new(eta, k, roughness)
+                    }
+                };
+                if let Some(v) = fields.get("film") {
+                    let mut film = Fields::new(&self.file, &fields.field("film"), v)?;
+                    conductor.set_film(self.thin_film(&mut film, v, "ior")?);
+                    film.finish()?;
+                }
+                Arc::new(conductor)
+            }
+            // 给出 roughness 或 absorption 时使用支持磨砂和吸收的微表面模型。
+            // 给出 dispersion 时折射率随波长变化，这时不再需要 refraction_index
+            "dielectric" => {
+                let dispersion = match fields.get("dispersion") {
+                    Some(v) => Some(self.dispersion(v, &fields.field("dispersion"))?),
+                    None => None,
+                };
+                let ri = match (dispersion, fields.get("refraction_index")) {
+                    (Some(_), Some(v)) => {
+                        return Err(self.error(
+                            v,
+                            &fields.field("refraction_index"),
+                            "cannot be combined with \"dispersion\"",
+                        ));
+                    }
+                    (Some(d), None) => d.nominal_index(),
+                    (None, _) => {
+                        let ri = fields.required("refraction_index", value)?;
+                        self.positive(ri, &fields.field("refraction_index"))?
+                    }
+                };
+                let roughness = match fields.get("roughness") {
+                    Some(v) => {
+                        let field = fields.field("roughness");
+                        let r = self.number(v, &field)?;
+                        if !(0.0..=1.0).contains(&r) {
+                            return Err(self.error(v, &field, "must be between 0 and 1"));
+                        }
+                        Some(r)
+                    }
+                    None => None,
+                };
+                // absorption 是光在介质中走过 absorption_distance 后剩下的颜色
+                let absorption = match fields.get("absorption") {
+                    Some(v) => {
+                        let field = fields.field("absorption");
+                        let c = self.vec3(v, &field)?;
+                        if (0..3).any(|i| !(c[i] > 0.0 && c[i] <= 1.0)) {
+                            return Err(self.error(v, &field, "components must be in (0, 1]"));
+                        }
+                        Some(c)
+                    }
+                    None => None,
+                };
+                let distance = match fields.get("absorption_distance") {
+                    Some(v) => self.positive(v, &fields.field("absorption_distance"))?,
+                    None => 1.0,
+                };
+                if roughness.is_none() && absorption.is_none() {
+                    let mut glass = Dielectric::new(ri);
+                    if let Some(d) = dispersion {
+                        glass.set_dispersion(d);
+                    }
+                    Arc::new(glass)
+                } else {
+                    let mut glass = RoughDielectric::new(ri, roughness.unwrap_or(0.0));
+                    if let Some(color) = absorption {
+                        glass.set_absorption(color, distance);
+                    }
+                    if let Some(d) = dispersion {
+                        glass.set_dispersion(d);
+                    }
+                    Arc::new(glass)
+                }
+            }
+            "disney" => {
+                let base_color = fields.required("base_color", value)?;
+                let mut disney =
+                    Disney::new(self.texture(base_color, &fields.field("base_color"))?);
+                for (key, param) in [
+                    ("metallic", &mut disney.metallic),
+                    ("roughness", &mut disney.roughness),
+                    ("specular", &mut disney.specular),
+                    ("specular_tint", &mut disney.specular_tint),
+                    ("sheen", &mut disney.sheen),
+                    ("sheen_tint", &mut disney.sheen_tint),
+                    ("clearcoat", &mut disney.clearcoat),
+                    ("clearcoat_gloss", &mut disney.clearcoat_gloss),
+                    ("transmission", &mut disney.transmission),
+                    ("anisotropic", &mut disney.anisotropic),
+                ] {
+                    if let Some(v) = fields.get(key) {
+                        *param = self.scalar_texture(v, &fields.field(key))?;
+                    }
+                }
+                Arc::new(disney)
+            }
+            // 按遮罩混合两个材质，遮罩可以是 0 到 1 的数字或纹理
+            "mix" => {
+                let a = fields.required("a", value)?;
+                let a = self.material(a, &fields.field("a"))?;
+                let b = fields.required("b", value)?;
+                let b = self.material(b, &fields.field("b"))?;
+                let mask = fields.required("mask", value)?;
+                Arc::new(MixMaterial::new(
+                    a,
+                    b,
+                    self.scalar_texture(mask, &fields.field("mask"))?,
+                ))
+            }
+            // 在底层材质上覆盖清漆，tint 是垂直穿过清漆一个来回后剩下的颜色
+            // thin_film 是表面带薄膜的清漆；省略 base 时是悬空的薄膜，比如肥皂泡
+            "coated" | "thin_film" => {
+                let film = kind == "thin_film";
+                let free_standing = film && fields.get("base").is_none();
+                let base: MaterialRef = if free_standing {
+                    Arc::new(Transparent)
+                } else {
+                    let base = fields.required("base", value)?;
+                    self.material(base, &fields.field("base"))?
+                };
+                let ri = match fields.get("refraction_index") {
+                    Some(v) => self.positive(v, &fields.field("refraction_index"))?,
+                    None if free_standing => 1.0,
+                    None => 1.5,
+                };
+                let roughness = match fields.get("roughness") {
+                    Some(v) => {
+                        let field = fields.field("roughness");
+                        let r = self.number(v, &field)?;
+                        if !(0.0..=1.0).contains(&r) {
+                            return Err(self.error(v, &field, "must be between 0 and 1"));
+                        }
+                        r
+                    }
+                    None => 0.0,
+                };
+                let mut coated = CoatedMaterial::new(base, ri, roughness);
+                if let Some(v) = fields.get("tint") {
+                    let field = fields.field("tint");
+                    let c = self.vec3(v, &field)?;
+                    if (0..3).any(|i| !(c[i] > 0.0 && c[i] <= 1.0)) {
+                        return Err(self.error(v, &field, "components must be in (0, 1]"));
+                    }
+                    coated.set_tint(c);
+                }
+                if film {
+                    coated.set_film(self.thin_film(&mut fields, value, "film_ior")?);
+                }
+                Arc::new(coated)
+            }
+            "diffuse_light" => {
+                let emit = fields.required("emit", value)?;
+                Arc::new(DiffuseLight::new(
+                    self.texture(emit, &fields.field("emit"))?,
+                ))
+            }
+            "isotropic" => {
+                let albedo = fields.required("albedo", value)?;
+                Arc::new(Isotropic::new(
+                    self.texture(albedo, &fields.field("albedo"))?,
+                ))
+            }
+            // 各向异性的相函数，给出 g2 时是两个波瓣的混合，weight 是第二个波瓣的比例
+            "henyey_greenstein" => {
+                let albedo = fields.required("albedo", value)?;
+                let albedo = self.texture(albedo, &fields.field("albedo"))?;
+                let g = fields.required("g", value)?;
+                let g = self.asymmetry(g, &fields.field("g"))?;
+                match fields.get("g2") {
+                    Some(g2) => {
+                        let g2 = self.asymmetry(g2, &fields.field("g2"))?;
+                        let weight = match fields.get("weight") {
+                            Some(v) => {
+                                let field = fields.field("weight");
+                                let w = self.number(v, &field)?;
+                                if !(0.0..=1.0).contains(&w) {
+                                    return Err(self.error(v, &field, "must be between 0 and 1"));
+                                }
+                                w
+                            }
+                            None => 0.5,
+                        };
+                        Arc::new(HenyeyGreenstein::new_double(albedo, g, g2, weight))
+                    }
+                    None => Arc::new(HenyeyGreenstein::new(albedo, g)),
+                }
+            }
+            other => {
+                return Err(self.error(
+                    value,
+                    &fields.field("type"),
+                    format!("unknown material type \"{}\"", other),
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok(mat)
+    }
+
+    fn object_list(&self, value: &Json, path: &str) -> Result<HittableList, SceneError> {
+        let mut list = HittableList::default();
+        for (i, item) in self.array(value, path)?.iter().enumerate() {
+            list.add(self.object(item, &format!("{}[{}]", path, i))?);
+        }
+        Ok(list)
+    }
+
+    fn object(&self, value: &Json, path: &str) -> Result<HittableRef, SceneError> {
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let kind = self.kind(&mut fields, value)?;
+
+        macro_rules! vec3 {
+            ($key:expr) => {{
+                let v = fields.required($key, value)?;
+                self.vec3(v, &fields.field($key))?
+            }};
+        }
+        macro_rules! material {
+            () => {{
+                let v = fields.required("material", value)?;
+                self.material(v, &fields.field("material"))?
+            }};
+        }
+
+        let object: HittableRef = match kind {
+            "sphere" => {
+                let center = vec3!("center");
+                let radius = fields.required("radius", value)?;
+                let radius = self.positive(radius, &fields.field("radius"))?;
+                match fields.get("center2") {
+                    Some(c2) => {
+                        let center2 = self.vec3(c2, &fields.field("center2"))?;
+                        Arc::new(Sphere::new_move(center, center2, radius, material!()))
+                    }
+                    None => Arc::new(Sphere::new(center, radius, material!())),
+                }
+            }
+            "quad" => Arc::new(Quad::new(vec3!("q"), vec3!("u"), vec3!("v"), material!())),
+            "box" => box_(vec3!("a"), vec3!("b"), material!()),
+            "triangle" => {
+                let (v0, v1, v2) = (vec3!("v0"), vec3!("v1"), vec3!("v2"));
+                let triangle = Triangle::try_new(v0, v1, v2, material!())
+                    .ok_or_else(|| self.error(value, path, "triangle has zero area"))?;
+                Arc::new(triangle)
+            }
+            "obj" => {
+                let field = fields.field("file");
+                let file = fields.required("file", value)?;
+                let name = self.string(file, &field)?;
+                load_obj(&self.resolve(name).to_string_lossy(), self.bvh)
+                    .map_err(|e| self.error(file, &field, e.to_string()))?
+            }
+            "translate" => {
+                let offset = vec3!("offset");
+                let inner = fields.required("object", value)?;
+                Arc::new(Translate::new(
+                    self.transformed(inner, &fields.field("object"))?,
+                    offset,
+                ))
+            }
+            "rotate_y" => {
+                let angle = fields.required("angle", value)?;
+                let angle = self.number(angle, &fields.field("angle"))?;
+                let inner = fields.required("object", value)?;
+                Arc::new(RotateY::new(
+                    self.transformed(inner, &fields.field("object"))?,
+                    angle,
+                ))
+            }
+            // 介质用 albedo 给出各向同性散射的颜色，或者用 phase 指定相函数材质
+            "constant_medium" => {
+                let boundary = fields.required("boundary", value)?;
+                let boundary = self.object(boundary, &fields.field("boundary"))?;
+                let density = fields.required("density", value)?;
+                let density = self.positive(density, &fields.field("density"))?;
+                let phase = self.phase_function(&mut fields, value)?;
+                Arc::new(ConstantMedium::new_with_phase(boundary, density, phase))
+            }
+            // 非均匀介质，density 是体素网格或程序化的密度场
+            "heterogeneous_medium" => {
+                let boundary = fields.required("boundary", value)?;
+                let boundary = self.object(boundary, &fields.field("boundary"))?;
+                let density = fields.required("density", value)?;
+                let density = self.density_field(density, &fields.field("density"))?;
+                let phase = self.phase_function(&mut fields, value)?;
+                Arc::new(HeterogeneousMedium::new_with_phase(
+                    boundary, density, phase,
+                ))
+            }
+            // 次表面散射物体，mean_free_path 是每个颜色通道的平均自由程
+            "subsurface" => {
+                if self.transforms.get() > 0 {
+                    return Err(self.error(
+                        value,
+                        path,
+                        "subsurface objects cannot be transformed; transform the boundary instead",
+                    ));
+                }
+                let boundary = fields.required("boundary", value)?;
+                let boundary = self.object(boundary, &fields.field("boundary"))?;
+                let mfp = fields.required("mean_free_path", value)?;
+                let field = fields.field("mean_free_path");
+                let mean_free_path = self.vec3(mfp, &field)?;
+                if (0..3).any(|i| mean_free_path[i] <= 0.0) {
+                    return Err(self.error(mfp, &field, "components must be positive"));
+                }
+                let albedo = fields.required("albedo", value)?;
+                let field = fields.field("albedo");
+                let c = self.vec3(albedo, &field)?;
+                if (0..3).any(|i| !(0.0..=1.0).contains(&c[i])) {
+                    return Err(self.error(albedo, &field, "components must be in [0, 1]"));
+                }
+                let ri = match fields.get("refraction_index") {
+                    Some(v) => self.positive(v, &fields.field("refraction_index"))?,
+                    None => 1.4,
+                };
+                Arc::new(Subsurface::new(boundary, mean_free_path, c, ri))
+            }
+            "list" | "bvh" => {
+                let field = fields.field("objects");
+                let objects = fields.required("objects", value)?;
+                let mut list = self.object_list(objects, &field)?;
+                if list.objects.is_empty() {
+                    return Err(self.error(objects, &field, "must not be empty"));
+                }
+                if kind == "bvh" {
+                    let split = match fields.get("split") {
+                        Some(v) => {
+                            let field = fields.field("split");
+                            let name = self.string(v, &field)?;
+                            BvhSplit::from_name(name).ok_or_else(|| {
+                                self.error(
+                                    v,
+                                    &field,
+                                    format!("expected \"median\" or \"sah\", got \"{}\"", name),
+                                )
+                            })?
+                        }
+                        None => self.bvh.split,
+                    };
+                    let layout = match fields.get("layout") {
+                        Some(v) => {
+                            let field = fields.field("layout");
+                            let name = self.string(v, &field)?;
+                            BvhLayout::from_name(name).ok_or_else(|| {
+                                self.error(
+                                    v,
+                                    &field,
+                                    format!("expected \"tree\" or \"flat\", got \"{}\"", name),
+                                )
+                            })?
+                        }
+                        None => self.bvh.layout,
+                    };
+                    build_bvh(&mut list, BvhConfig { split, layout })
+                } else {
+                    Arc::new(list)
+                }
+            }
+            other => {
+                return Err(self.error(
+                    value,
+                    &fields.field("type"),
+                    format!("unknown object type \"{}\"", other),
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok(object)
+    }
+
+    // 次表面散射的随机游走在 boundary 的坐标中进行，放在变换里面会得到错误的结果，
+    // 所以记下当前的变换层数，让 "subsurface" 报错
+    fn transformed(&self, value: &Json, path: &str) -> Result<HittableRef, SceneError> {
+        self.transforms.set(self.transforms.get() + 1);
+        let object = self.object(value, path);
+        self.transforms.set(self.transforms.get() - 1);
+        object
+    }
+
+    fn phase_function<'v>(
+        &self,
+        fields: &mut Fields<'v>,
+        object: &Json,
+    ) -> Result<MaterialRef, SceneError> {
+        match (fields.get("phase"), fields.get("albedo")) {
+            (Some(_), Some(albedo)) => Err(self.error(
+                albedo,
+                &fields.field("albedo"),
+                "cannot be combined with \"phase\"",
+            )),
+            (Some(phase), None) => self.material(phase, &fields.field("phase")),
+            (None, _) => {
+                let albedo = fields.required("albedo", object)?;
+                let albedo = self.texture(albedo, &fields.field("albedo"))?;
+                Ok(Arc::new(Isotropic::new(albedo)))
+            }
+        }
+    }
+
+    // "grid" 从 Mitsuba 的 .vol 文件读取体素网格，scale 乘到每个体素上；
+    // "noise" 是 Perlin 湍流，density 是最大密度，scale 是噪声的频率
+    fn density_field(&self, value: &Json, path: &str) -> Result<DensityFieldRef, SceneError> {
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let kind = self.kind(&mut fields, value)?;
+        let field: DensityFieldRef = match kind {
+            "grid" => {
+                let scale = match fields.get("scale") {
+                    Some(v) => self.positive(v, &fields.field("scale"))?,
+                    None => 1.0,
+                };
+                let field = fields.field("file");
+                let file = fields.required("file", value)?;
+                let name = self.string(file, &field)?;
+                Arc::new(
+                    VoxelGrid::load(&self.resolve(name).to_string_lossy(), scale)
+                        .map_err(|e| self.error(file, &field, e))?,
+                )
+            }
+            "noise" => {
+                let density = fields.required("density", value)?;
+                let density = self.positive(density, &fields.field("density"))?;
+                let scale = match fields.get("scale") {
+                    Some(v) => self.positive(v, &fields.field("scale"))?,
+                    None => 1.0,
+                };
+                Arc::new(NoiseDensity::new(density, scale))
+            }
+            other => {
+                return Err(self.error(
+                    value,
+                    &fields.field("type"),
+                    format!("unknown density type \"{}\"", other),
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok(field)
+    }
+
+    // lights 列表中的点光源、聚光灯和平行光，其他类型返回 None，按普通物体解析
+    fn delta_light(&self, value: &Json, path: &str) -> Result<Option<DeltaLightRef>, SceneError> {
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let kind = self.kind(&mut fields, value)?;
+        if !matches!(kind, "point" | "spot" | "directional") {
+            return Ok(None);
+        }
+
+        macro_rules! vec3 {
+            ($key:expr) => {{
+                let v = fields.required($key, value)?;
+                self.vec3(v, &fields.field($key))?
+            }};
+        }
+        let mut angle = |key: &str, default: f64| -> Result<f64, SceneError> {
+            let Some(v) = fields.get(key) else {
+                return Ok(default);
+            };
+            let degrees = self.number(v, &fields.field(key))?;
+            if degrees > 0.0 && degrees <= 180.0 {
+                Ok(degrees)
+            } else {
+                Err(self.error(v, &fields.field(key), "must be between 0 and 180 degrees"))
+            }
+        };
+
+        let light: DeltaLightRef = match kind {
+            "point" => Arc::new(PointLight::new(vec3!("position"), vec3!("intensity"))),
+            "spot" => {
+                let cone_angle = angle("cone_angle", 30.0)?;
+                let falloff_start = angle("falloff_start", cone_angle * 0.8)?;
+                Arc::new(SpotLight::new(
+                    vec3!("position"),
+                    vec3!("direction"),
+                    vec3!("intensity"),
+                    cone_angle,
+                    falloff_start,
+                ))
+            }
+            _ => Arc::new(DirectionalLight::new(
+                vec3!("direction"),
+                vec3!("irradiance"),
+            )),
+        };
+        fields.finish()?;
+        Ok(Some(light))
+    }
+
+    fn environment(&self, value: &Json, path: &str) -> Result<Arc<EnvironmentLight>, SceneError> {
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let field = fields.field("file");
+        let file = fields.required("file", value)?;
+        let name = self.string(file, &field)?;
+        let image = HdrImage::try_new(&self.resolve(name).to_string_lossy())
+            .or_else(|| HdrImage::try_new(name))
+            .ok_or_else(|| self.error(file, &field, format!("cannot load image \"{}\"", name)))?;
+        let intensity = match fields.get("intensity") {
+            Some(v) => self.positive(v, &fields.field("intensity"))?,
+            None => 1.0,
+        };
+        let rotation = match fields.get("rotation") {
+            Some(v) => self.number(v, &fields.field("rotation"))?,
+            None => 0.0,
+        };
+        fields.finish()?;
+        Ok(Arc::new(EnvironmentLight::new(image, intensity, rotation)))
+    }
+
+    fn sky(&self, value: &Json, path: &str) -> Result<Arc<PhysicalSky>, SceneError> {
+        let mut fields = Fields::new(&self.file, path, value)?;
+        let mut number = |key: &str, default: f64| match fields.get(key) {
+            Some(v) => self.number(v, &fields.field(key)),
+            None => Ok(default),
+        };
+        let elevation = number("sun_elevation", 45.0)?;
+        let azimuth = number("sun_azimuth", 0.0)?;
+        let turbidity = number("turbidity", 3.0)?;
+        let intensity = number("intensity", 1.0)?;
+        let sun_radius = number("sun_radius", 1.0)?;
+        let sun_intensity = number("sun_intensity", 1.0)?;
+        if !(-90.0..=90.0).contains(&elevation) {
+            let v = fields.get("sun_elevation").unwrap();
+            return Err(self.error(
+                v,
+                &fields.field("sun_elevation"),
+                "must be between -90 and 90 degrees",
+            ));
+        }
+        // Preetham 的拟合只在 2 到 10 的浑浊度之间有效
+        if !(2.0..=10.0).contains(&turbidity) {
+            let v = fields.get("turbidity").unwrap();
+            return Err(self.error(v, &fields.field("turbidity"), "must be between 2 and 10"));
+        }
+        if !(sun_radius > 0.0 && sun_radius < 90.0) {
+            let v = fields.get("sun_radius").unwrap();
+            return Err(self.error(
+                v,
+                &fields.field("sun_radius"),
+                "must be between 0 and 90 degrees",
+            ));
+        }
+        for (key, value) in [("intensity", intensity), ("sun_intensity", sun_intensity)] {
+            if value < 0.0 {
+                let v = fields.get(key).unwrap();
+                return Err(self.error(v, &fields.field(key), "must not be negative"));
+            }
+        }
+
+        let mut sky = PhysicalSky::new(elevation, azimuth, turbidity);
+        sky.set_intensity(intensity);
+        sky.set_sun(sun_radius, sun_intensity);
+        if let Some(v) = fields.get("ground") {
+            sky.set_ground(self.vec3(v, &fields.field("ground"))?);
+        }
+        fields.finish()?;
+        Ok(Arc::new(sky))
+    }
+
+    fn camera(&self, value: &Json, path: &str) -> Result<Camera, SceneError> {
+        let mut cam = Camera::default();
+        let mut fields = Fields::new(&self.file, path, value)?;
+
+        for key in ["aspect_ratio", "vfov", "defocus_angle", "focus_dist"] {
+            let Some(v) = fields.get(key) else {
+                continue;
+            };
+            let field = fields.field(key);
+            let n = match key {
+                "defocus_angle" => self.number(v, &field)?,
+                _ => self.positive(v, &field)?,
+            };
+            match key {
+                "aspect_ratio" => cam.aspect_ratio = n,
+                "vfov" => cam.vfov = n,
+                "defocus_angle" => cam.defocus_angle = n,
+                _ => cam.focus_dist = n,
+            }
+        }
+        for key in ["image_width", "samples_per_pixel", "max_depth"] {
+            let Some(v) = fields.get(key) else {
+                continue;
+            };
+            let n = self.integer(v, &fields.field(key))?;
+            match key {
+                "image_width" => cam.image_width = n,
+                "samples_per_pixel" => cam.samples_per_pixel = n,
+                _ => cam.max_depth = n,
+            }
+        }
+        if let Some(v) = fields.get("exposure") {
+            cam.display.exposure = self.number(v, &fields.field("exposure"))?;
+        }
+        if let Some(v) = fields.get("white_point") {
+            cam.display.white_point = Some(self.positive(v, &fields.field("white_point"))?);
+        }
+        if let Some(v) = fields.get("tone_mapping") {
+            let field = fields.field("tone_mapping");
+            let name = self.string(v, &field)?;
+            cam.display.tone_mapper = ToneMapper::from_name(name).ok_or_else(|| {
+                self.error(v, &field, format!("unknown tone mapper \"{}\"", name))
+            })?;
+        }
+        // 俄罗斯轮盘赌的起始反弹次数，写 "off" 表示关闭
+        if let Some(v) = fields.get("rr_depth") {
+            let field = fields.field("rr_depth");
+            cam.rr_depth = match &v.kind {
+                JsonKind::String(s) if s == "off" => None,
+                JsonKind::Number(n) if n.fract() == 0.0 && *n >= 0.0 && *n <= i32::MAX as f64 => {
+                    Some(*n as i32)
+                }
+                _ => {
+                    return Err(self.error(
+                        v,
+                        &field,
+                        "expected a non-negative integer or \"off\"",
+                    ));
+                }
+            };
+        }
+        if let Some(v) = fields.get("mis") {
+            let field = fields.field("mis");
+            let name = self.string(v, &field)?;
+            cam.mis = MisHeuristic::from_name(name).ok_or_else(|| {
+                self.error(
+                    v,
+                    &field,
+                    format!("expected \"balance\" or \"power\", got \"{}\"", name),
+                )
+            })?;
+        }
+        // 光谱模式，色散只在这个模式下可见
+        if let Some(v) = fields.get("spectral") {
+            cam.spectral = match v.kind {
+                JsonKind::Bool(b) => b,
+                _ => {
+                    return Err(self.error(
+                        v,
+                        &fields.field("spectral"),
+                        format!("expected a boolean, found {}", v.type_name()),
+                    ));
+                }
+            };
+        }
+        for key in ["lookfrom", "lookat", "vup", "background"] {
+            let Some(v) = fields.get(key) else {
+                continue;
+            };
+            let v = self.vec3(v, &fields.field(key))?;
+            match key {
+                "lookfrom" => cam.lookfrom = v,
+                "lookat" => cam.lookat = v,
+                "vup" => cam.vup = v,
+                _ => cam.background = v,
+            }
+        }
+
+        fields.finish()?;
+        Ok(cam)
+    }
+}
+
+// 场景文件的顶层结构：
+// { "camera": {...}, "textures": {名字: 纹理}, "materials": {名字: 材质},
+//   "objects": [...], "lights": [面光源或 {"type": "point" | "spot" | "directional", ...}],
+//   "environment": {"file": 环境贴图, "intensity": 亮度倍数, "rotation": 绕 y 轴旋转的角度},
+//   "sky": {"sun_elevation", "sun_azimuth", "turbidity", "intensity", "sun_radius", "sun_intensity", "ground"} }
+// 纹理和材质按书写顺序加载，后面的可以引用前面的名字
+pub fn load_scene(path: &str, bvh: BvhConfig) -> Result<Scene, SceneError> {
+    let text = std::fs::read_to_string(path).map_err(|e| SceneError {
+        file: path.to_string(),
+        line: 0,
+        column: 0,
+        field: String::new(),
+        message: e.to_string(),
+    })?;
+    let root = json::parse(&text).map_err(|e| SceneError {
+        file: path.to_string(),
+        line: e.line,
+        column: e.column,
+        field: String::new(),
+        message: e.message,
+    })?;
+
+    let mut loader = Loader {
+        file: path.to_string(),
+        dir: Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        bvh,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        transforms: Cell::new(0),
+    };
+    let mut fields = Fields::new(&loader.file, "", &root)?;
+    let file = loader.file.clone();
+
+    if let Some(textures) = fields.get("textures") {
+        let names = Fields::new(&file, "textures", textures)?;
+        for (key, value) in names.entries {
+            let tex = loader.texture(value, &format!("textures.{}", key.name))?;
+            loader.textures.insert(key.name.clone(), tex);
+        }
+    }
+    if let Some(materials) = fields.get("materials") {
+        let names = Fields::new(&file, "materials", materials)?;
+        for (key, value) in names.entries {
+            let mat = loader.material(value, &format!("materials.{}", key.name))?;
+            loader.materials.insert(key.name.clone(), mat);
+        }
+    }
+
+    let camera = match fields.get("camera") {
+        Some(value) => loader.camera(value, "camera")?,
+        None => Camera::default(),
+    };
+
+    let objects = fields.required("objects", &root)?;
+    let world = loader.object_list(objects, "objects")?;
+    if world.objects.is_empty() {
+        return Err(loader.error(objects, "objects", "scene has no objects"));
+    }
+
+    // 图像环境光和解析天空只能二选一
+    let environment: Option<(EnvironmentRef, HittableRef)> =
+        match (fields.get("environment"), fields.get("sky")) {
+            (Some(_), Some(sky)) => {
+                return Err(loader.error(sky, "sky", "cannot be combined with \"environment\""));
+            }
+            (Some(value), None) => {
+                let light = loader.environment(value, "environment")?;
+                Some((light.clone(), light))
+            }
+            (None, Some(value)) => {
+                let sky = loader.sky(value, "sky")?;
+                Some((sky.clone(), sky))
+            }
+            (None, None) => None,
+        };
+
+    // 光源列表中的面光源只用于重要性采样（它们也要出现在 objects 中），
+    // δ 光源只出现在这里。有环境光时可以省略，否则至少要有一个光源
+    let mut light_list = HittableList::default();
+    let mut delta_lights = Vec::new();
+    match (fields.get("lights"), &environment) {
+        (None, Some(_)) => {}
+        _ => {
+            let lights = fields.required("lights", &root)?;
+            let items = loader.array(lights, "lights")?;
+            if items.is_empty() {
+                return Err(loader.error(lights, "lights", "must contain at least one light"));
+            }
+            for (i, item) in items.iter().enumerate() {
+                let path = format!("lights[{}]", i);
+                match loader.delta_light(item, &path)? {
+                    Some(light) => delta_lights.push(light),
+                    None => light_list.add(loader.object(item, &path)?),
+                }
+            }
+        }
+    }
+
+    fields.finish()?;
+
+    let mut scene = Scene::with_environment(camera, world, light_list, environment);
+    scene.camera.delta_lights = delta_lights;
+    Ok(scene)
+}
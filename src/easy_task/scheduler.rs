@@ -0,0 +1,117 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::framebuffer::Framebuffer;
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// 图像上的一个矩形分块，[x0, x1) × [y0, y1)
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub index: usize,
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+}
+
+// 按从上到下、从左到右的顺序把图像切成边长为 tile_size 的分块，边缘的分块可能更小
+pub fn split_tiles(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(tile_size) {
+        for x0 in (0..width).step_by(tile_size) {
+            tiles.push(Tile {
+                index: tiles.len(),
+                x0,
+                y0,
+                x1: (x0 + tile_size).min(width),
+                y1: (y0 + tile_size).min(height),
+            });
+        }
+    }
+    tiles
+}
+
+// 0 表示使用全部 CPU 核心
+pub fn resolve_threads(threads: usize) -> usize {
+    if threads == 0 {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        threads
+    }
+}
+
+// 先取自己队列里的分块，空了就从全局队列批量领取，再不行就去偷其它线程的
+fn find_tile(
+    local: &Worker<Tile>,
+    global: &Injector<Tile>,
+    stealers: &[Stealer<Tile>],
+) -> Option<Tile> {
+    local.pop().or_else(|| {
+        std::iter::repeat_with(|| {
+            global
+                .steal_batch_and_pop(local)
+                .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+        })
+        .find(|s| !s.is_retry())
+        .and_then(Steal::success)
+    })
+}
+
+// 用固定大小的线程池渲染所有分块，render_tile 返回分块内按行优先排列的像素颜色
+pub fn render_tiles<F>(
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    threads: usize,
+    render_tile: F,
+) -> Framebuffer
+where
+    F: Fn(&Tile) -> Vec<Color> + Sync,
+{
+    let tiles = split_tiles(width, height, tile_size);
+    let total = tiles.len();
+    let threads = resolve_threads(threads).min(total.max(1));
+
+    let global = Injector::new();
+    for tile in tiles {
+        global.push(tile);
+    }
+    let workers: Vec<Worker<Tile>> = (0..threads).map(|_| Worker::new_fifo()).collect();
+    let stealers: Vec<Stealer<Tile>> = workers.iter().map(|w| w.stealer()).collect();
+
+    let framebuffer = Mutex::new(Framebuffer::new(width, height));
+    let finished = AtomicUsize::new(0);
+
+    crossbeam::scope(|scope| {
+        for local in workers {
+            let (global, stealers) = (&global, &stealers);
+            let (framebuffer, finished, render_tile) = (&framebuffer, &finished, &render_tile);
+            scope.spawn(move |_| {
+                while let Some(tile) = find_tile(&local, global, stealers) {
+                    let pixels = render_tile(&tile);
+                    // 在持有锁时更新进度，保证打印出的剩余数量单调递减
+                    let mut framebuffer = framebuffer.lock().unwrap();
+                    framebuffer.write_tile(&tile, &pixels);
+                    let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                    eprint!("\rTiles remaining: {:<8}", total - done);
+                    std::io::stderr().flush().ok();
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    framebuffer.into_inner().unwrap()
+}
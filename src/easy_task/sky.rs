@@ -0,0 +1,249 @@
+use crate::easy_task::aabb::{self, Aabb};
+use crate::easy_task::color::Color;
+use crate::easy_task::environment::Environment;
+use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::interval::Interval;
+use crate::easy_task::onb::Onb;
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rtweekend::{PI, degrees_to_radians, random_double};
+use crate::easy_task::vec3::{Point3, Vec3, dot, unit_vector};
+
+// Preetham 模型的亮度单位是 kcd/m²，乘上这个系数后晴天天顶的辐射度大约为 1
+const SKY_SCALE: f64 = 0.05;
+// 太阳视半径约 0.27°，为了降低噪声默认放大一些
+const DEFAULT_SUN_RADIUS: f64 = 1.0;
+
+// Perez 天空亮度分布 F(θ, γ)，θ 是视线的天顶角，γ 是视线与太阳的夹角
+#[derive(Debug, Clone, Copy)]
+struct Perez([f64; 5]);
+
+impl Perez {
+    fn eval(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / cos_theta).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+}
+
+// 天顶角为 theta_sun 时天顶的 CIE xy 色度，系数取自 Preetham 等人 1999 年的论文
+fn zenith_chromaticity(rows: [[f64; 4]; 3], turbidity: f64, theta_sun: f64) -> f64 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let th = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+    (0..3)
+        .map(|i| t[i] * (0..4).map(|j| rows[i][j] * th[j]).sum::<f64>())
+        .sum()
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::default();
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    Color::new(
+        3.2406 * cx - 1.5372 * luminance - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * luminance + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * luminance + 1.0570 * cz,
+    )
+}
+
+// 太阳方向：elevation 是高出地平线的角度，azimuth 从 -z 方向开始向 +x 方向量
+pub fn sun_direction(elevation_degrees: f64, azimuth_degrees: f64) -> Vec3 {
+    let elevation = degrees_to_radians(elevation_degrees);
+    let azimuth = degrees_to_radians(azimuth_degrees);
+    Vec3::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        -elevation.cos() * azimuth.cos(),
+    )
+}
+
+// 大气对直射阳光的透过率（只考虑瑞利散射和气溶胶），分别在 680、550、440nm 处计算
+fn sun_transmittance(turbidity: f64, theta_sun: f64) -> Color {
+    let theta_degrees = theta_sun.to_degrees().min(93.0);
+    // 相对光学质量，Kasten 的近似公式
+    let mass = 1.0 / (theta_sun.cos().max(0.0) + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let channel = |lambda: f64| {
+        let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+        let aerosol = (-beta * lambda.powf(-1.3) * mass).exp();
+        rayleigh * aerosol
+    };
+    Color::new(channel(0.68), channel(0.55), channel(0.44))
+}
+
+// Preetham 解析天空模型加上一个可以被直接采样的太阳圆盘。
+// 和 EnvironmentLight 一样，它实现 Hittable 是为了进入 lights 列表，只对太阳圆盘做重要性采样
+#[derive(Debug, Clone)]
+pub struct PhysicalSky {
+    sun: Vec3,
+    theta_sun: f64,
+    turbidity: f64,
+    perez: [Perez; 3], // 亮度 Y 和色度 x、y
+    zenith: [f64; 3],
+    intensity: f64,
+    sun_radiance: Color,
+    sun_cos_max: f64,
+    ground: Color,
+}
+
+impl PhysicalSky {
+    pub fn new(elevation_degrees: f64, azimuth_degrees: f64, turbidity: f64) -> Self {
+        let t = turbidity;
+        let sun = sun_direction(elevation_degrees, azimuth_degrees);
+        let theta_sun = sun.y().clamp(-1.0, 1.0).acos();
+
+        let perez = [
+            Perez([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        // 太阳落到地平线以下时按地平线处理，天空逐渐变暗由 Perez 分布自身完成
+        let theta_clamped = theta_sun.min(PI / 2.0);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_clamped);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = zenith_chromaticity(
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+            t,
+            theta_clamped,
+        );
+        let zenith_y = zenith_chromaticity(
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+            t,
+            theta_clamped,
+        );
+
+        let mut sky = Self {
+            sun,
+            theta_sun,
+            turbidity,
+            perez,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            intensity: 1.0,
+            sun_radiance: Color::default(),
+            sun_cos_max: 1.0,
+            ground: Color::new(0.3, 0.3, 0.3),
+        };
+        sky.set_sun(DEFAULT_SUN_RADIUS, 1.0);
+        sky
+    }
+
+    // 天空（不含太阳）的整体亮度倍数
+    pub fn set_intensity(&mut self, intensity: f64) {
+        self.intensity = intensity;
+    }
+
+    // 设置太阳圆盘的视半径（度）和亮度倍数。
+    // 圆盘的总照度与视半径无关，倍数为 1 时大气层外的照度是天顶辐射度的 10π 倍
+    pub fn set_sun(&mut self, radius_degrees: f64, intensity: f64) {
+        self.sun_cos_max = degrees_to_radians(radius_degrees).cos();
+        let solid_angle = 2.0 * PI * (1.0 - self.sun_cos_max);
+        let illuminance = 10.0 * PI * self.zenith[0].max(1.0) * SKY_SCALE;
+        self.sun_radiance = if self.sun.y() > 0.0 {
+            intensity * illuminance / solid_angle
+                * sun_transmittance(self.turbidity, self.theta_sun)
+        } else {
+            Color::default()
+        };
+    }
+
+    // 地平线以下的方向返回地平线处的天空颜色乘以这个反射率
+    pub fn set_ground(&mut self, albedo: Color) {
+        self.ground = albedo;
+    }
+
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        let below = direction.y() < 0.0;
+        let mut d = direction;
+        if below {
+            d = Vec3::new(d.x(), 0.0, d.z());
+            if d.length_squared() == 0.0 {
+                d = Vec3::new(1.0, 0.0, 0.0);
+            }
+            d = unit_vector(d);
+        }
+        let cos_theta = d.y().max(0.01);
+        let gamma = dot(d, self.sun).clamp(-1.0, 1.0).acos();
+
+        let mut values = [0.0; 3];
+        for (k, value) in values.iter_mut().enumerate() {
+            let ratio = self.perez[k].eval(cos_theta, gamma)
+                / self.perez[k].eval(1.0, self.theta_sun.min(PI / 2.0));
+            *value = self.zenith[k] * ratio;
+        }
+        let [luminance, x, y] = values;
+        let rgb = xyy_to_rgb(x, y, luminance);
+        let rgb = Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0));
+        let sky = self.intensity * SKY_SCALE * rgb;
+        if below { self.ground * sky } else { sky }
+    }
+
+    fn in_sun(&self, direction: Vec3) -> bool {
+        dot(unit_vector(direction), self.sun) >= self.sun_cos_max
+    }
+}
+
+impl Environment for PhysicalSky {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let sky = self.sky_radiance(unit_vector(direction));
+        if self.in_sun(direction) {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+}
+
+impl Hittable for PhysicalSky {
+    fn hit(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &aabb::EMPTY
+    }
+
+    // 在太阳圆盘对应的圆锥内均匀采样
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        if self.in_sun(direction) {
+            1.0 / (2.0 * PI * (1.0 - self.sun_cos_max))
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        let cos_theta = 1.0 - random_double() * (1.0 - self.sun_cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        Onb::new_from_w(self.sun).local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
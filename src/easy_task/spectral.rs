@@ -0,0 +1,304 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::rtweekend::random_double;
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+// 光谱模式采样的可见光波长范围（nm）
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+thread_local! {
+    // 当前线程正在追踪的路径所带的波长，None 表示 RGB 模式
+    static CURRENT: Cell<Option<SampledWavelengths>> = const { Cell::new(None) };
+}
+
+pub fn set_current(wavelengths: Option<SampledWavelengths>) {
+    CURRENT.with(|c| c.set(wavelengths));
+}
+
+pub fn current() -> Option<SampledWavelengths> {
+    CURRENT.with(|c| c.get())
+}
+
+// 反射率这类无量纲的 RGB 值在当前路径各个波长上的值，RGB 模式下原样返回
+pub fn reflectance(rgb: Color) -> Color {
+    match current() {
+        Some(wavelengths) => wavelengths.reflectance(rgb),
+        None => rgb,
+    }
+}
+
+// 当前路径的主波长，材质用它计算随波长变化的折射率；RGB 模式下为 None
+pub fn hero_wavelength() -> Option<f64> {
+    current().map(|w| w.lambda[0])
+}
+
+// 散射方向随波长变化时，其他波长不能沿同一条路径继续，只保留主波长。
+// 返回要乘到路径通量上的系数：第一次丢弃时主波长乘以 3 补偿丢掉的两个样本，之后只是保持
+pub fn terminate_secondary() -> Color {
+    CURRENT.with(|c| match c.get() {
+        Some(w) if !w.secondary_terminated => {
+            c.set(Some(SampledWavelengths {
+                secondary_terminated: true,
+                ..w
+            }));
+            Color::new(3.0, 0.0, 0.0)
+        }
+        Some(_) => Color::new(1.0, 0.0, 0.0),
+        None => Color::new(1.0, 1.0, 1.0),
+    })
+}
+
+// CIE 1931 标准观察者配色函数的多波瓣高斯拟合（Wyman 等人 2013）
+fn cmf(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, s1: f64, s2: f64| {
+        let t = (lambda - mu) / if lambda < mu { s1 } else { s2 };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+fn xyz_to_linear_srgb(xyz: [f64; 3]) -> Color {
+    let [x, y, z] = xyz;
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+// 色温 6504K 的黑体辐射，作为 D65 白点光源的近似，只用到相对形状
+fn illuminant_shape(lambda: f64) -> f64 {
+    const C2: f64 = 1.4388e7; // 第二辐射常数 hc/k，单位 nm·K
+    let t = 6504.0;
+    let l = lambda / 560.0;
+    1.0 / (l.powi(5) * ((C2 / (lambda * t)).exp() - 1.0))
+}
+
+fn smoothstep(a: f64, b: f64, x: f64) -> f64 {
+    let t = ((x - a) / (b - a)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// 把 RGB 线性地展开成光谱：三个平滑的基函数处处相加为 1，
+// 所以白色是平坦的光谱，[0, 1] 内的颜色展开后也在 [0, 1] 内
+fn rgb_basis(lambda: f64) -> Color {
+    let blue = 1.0 - smoothstep(460.0, 520.0, lambda);
+    let red = smoothstep(560.0, 620.0, lambda);
+    Color::new(red, 1.0 - red - blue, blue)
+}
+
+struct Calibration {
+    illuminant_scale: f64, // 让 RGB 为 (1, 1, 1) 的光源亮度 Y = 1
+    white: Color,          // 白色光源在胶片上的 sRGB，用来做白平衡
+}
+
+fn calibration() -> &'static Calibration {
+    static CALIBRATION: OnceLock<Calibration> = OnceLock::new();
+    CALIBRATION.get_or_init(|| {
+        const STEPS: usize = 4000;
+        let dl = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+        let mut xyz = [0.0; 3];
+        for i in 0..STEPS {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * dl;
+            let s = illuminant_shape(lambda) * dl;
+            for (sum, c) in xyz.iter_mut().zip(cmf(lambda)) {
+                *sum += c * s;
+            }
+        }
+        let scale = 1.0 / xyz[1];
+        Calibration {
+            illuminant_scale: scale,
+            white: xyz_to_linear_srgb(xyz.map(|v| v * scale)),
+        }
+    })
+}
+
+// 把随波长变化的反射率换算成 RGB 反射率：在白点光源下按配色函数积分并做白平衡，
+// 平坦的光谱 1 正好得到 (1, 1, 1)
+pub fn reflectance_to_rgb(reflectance: impl Fn(f64) -> f64) -> Color {
+    static WEIGHTS: OnceLock<Vec<(f64, Color)>> = OnceLock::new();
+    let weights = WEIGHTS.get_or_init(|| {
+        const STEPS: usize = 40;
+        let dl = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+        let mut weights: Vec<(f64, Color)> = (0..STEPS)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + (i as f64 + 0.5) * dl;
+                let s = illuminant_shape(lambda) * dl;
+                (lambda, xyz_to_linear_srgb(cmf(lambda).map(|c| c * s)))
+            })
+            .collect();
+        let total = weights
+            .iter()
+            .fold(Color::default(), |sum, &(_, w)| sum + w);
+        for (_, w) in weights.iter_mut() {
+            *w = Color::new(w.x() / total.x(), w.y() / total.y(), w.z() / total.z());
+        }
+        weights
+    });
+    let rgb = weights.iter().fold(Color::default(), |sum, &(lambda, w)| {
+        sum + reflectance(lambda) * w
+    });
+    Color::new(
+        rgb.x().clamp(0.0, 1.0),
+        rgb.y().clamp(0.0, 1.0),
+        rgb.z().clamp(0.0, 1.0),
+    )
+}
+
+// 一条路径同时追踪的三个波长：主波长均匀采样，另外两个在波长范围内等距错开。
+// 路径上的 Color 三个分量依次是这三个波长上的光谱值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; 3],
+    secondary_terminated: bool,
+}
+
+impl SampledWavelengths {
+    pub fn sample() -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = random_double() * range;
+        Self {
+            lambda: [0.0, 1.0, 2.0].map(|i| LAMBDA_MIN + (hero + i * range / 3.0) % range),
+            secondary_terminated: false,
+        }
+    }
+
+    // 反射率、透过率这类无量纲的 RGB 值在这几个波长上的光谱值
+    pub fn reflectance(&self, rgb: Color) -> Color {
+        Color::new(
+            dot_basis(rgb, self.lambda[0]),
+            dot_basis(rgb, self.lambda[1]),
+            dot_basis(rgb, self.lambda[2]),
+        )
+    }
+
+    // 发光的 RGB 值：按反射率展开后乘以白点光源的光谱
+    pub fn illuminant(&self, rgb: Color) -> Color {
+        let scale = calibration().illuminant_scale;
+        let s = self.reflectance(rgb);
+        Color::new(
+            s.x() * illuminant_shape(self.lambda[0]) * scale,
+            s.y() * illuminant_shape(self.lambda[1]) * scale,
+            s.z() * illuminant_shape(self.lambda[2]) * scale,
+        )
+    }
+
+    // 把路径在三个波长上的辐射度换算成 XYZ，再转成白平衡后的线性 sRGB
+    pub fn to_rgb(self, radiance: Color) -> Color {
+        let weight = (LAMBDA_MAX - LAMBDA_MIN) / 3.0; // 每个波长的 1/pdf 除以样本数
+        let mut xyz = [0.0; 3];
+        for (i, &lambda) in self.lambda.iter().enumerate() {
+            for (sum, c) in xyz.iter_mut().zip(cmf(lambda)) {
+                *sum += c * radiance[i] * weight;
+            }
+        }
+        let rgb = xyz_to_linear_srgb(xyz);
+        let white = calibration().white;
+        Color::new(
+            rgb.x() / white.x(),
+            rgb.y() / white.y(),
+            rgb.z() / white.z(),
+        )
+    }
+}
+
+fn dot_basis(rgb: Color, lambda: f64) -> f64 {
+    let b = rgb_basis(lambda);
+    rgb.x() * b.x() + rgb.y() * b.y() + rgb.z() * b.z()
+}
+
+// 电介质折射率随波长的变化，波长单位是 nm，公式中用 μm
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+// 常见玻璃的 Sellmeier 系数
+pub const GLASSES: [(&str, Dispersion); 3] = [
+    (
+        "bk7",
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        },
+    ),
+    (
+        "fused_silica",
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        },
+    ),
+    (
+        "sf11",
+        Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        },
+    ),
+];
+
+impl Dispersion {
+    pub fn preset(name: &str) -> Option<Self> {
+        GLASSES.iter().find(|(n, _)| *n == name).map(|(_, d)| *d)
+    }
+
+    pub fn refraction_index(&self, lambda: f64) -> f64 {
+        let l2 = (lambda / 1000.0) * (lambda / 1000.0);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+
+    // RGB 模式下使用 d 线（587.6 nm）处的折射率
+    pub fn nominal_index(&self) -> f64 {
+        self.refraction_index(587.6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glass_presets_match_catalogue() {
+        let bk7 = Dispersion::preset("bk7").unwrap();
+        // Schott N-BK7 在 d、F、C 三条谱线处的折射率和阿贝数
+        let (n_d, n_f, n_c) = (
+            bk7.refraction_index(587.6),
+            bk7.refraction_index(486.1),
+            bk7.refraction_index(656.3),
+        );
+        assert!((n_d - 1.5168).abs() < 1e-4, "{}", n_d);
+        assert!((n_f - 1.5224).abs() < 1e-4, "{}", n_f);
+        assert!((n_c - 1.5143).abs() < 1e-4, "{}", n_c);
+        let abbe = (n_d - 1.0) / (n_f - n_c);
+        assert!((abbe - 64.17).abs() < 0.2, "{}", abbe);
+        assert_eq!(bk7.nominal_index(), n_d);
+
+        let fused_silica = Dispersion::preset("fused_silica").unwrap();
+        assert!((fused_silica.nominal_index() - 1.4585).abs() < 1e-4);
+        let sf11 = Dispersion::preset("sf11").unwrap();
+        assert!((sf11.nominal_index() - 1.7847).abs() < 1e-4);
+        assert!(Dispersion::preset("unknown").is_none());
+    }
+
+    #[test]
+    fn cauchy_decreases_with_wavelength() {
+        let glass = Dispersion::Cauchy { a: 1.5, b: 0.005 };
+        assert!((glass.refraction_index(500.0) - 1.52).abs() < 1e-12);
+        assert!(glass.refraction_index(450.0) > glass.refraction_index(650.0));
+    }
+}
@@ -0,0 +1,330 @@
+use crate::easy_task::aabb::Aabb;
+use crate::easy_task::color::Color;
+use crate::easy_task::constant_medium::free_flight_distance;
+use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::interval::Interval;
+use crate::easy_task::material::{Material, ScatterRecord};
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rough_dielectric::fresnel_dielectric;
+use crate::easy_task::rtweekend::{INFINITY, random_double};
+use crate::easy_task::vec3::{Vec3, dot, random_unit_vector, reflect, refract, unit_vector};
+use std::sync::Arc;
+
+// 一条光线在介质里最多经历这么多次散射，超过时当作被吸收
+const MAX_EVENTS: usize = 4096;
+
+// 由希望看到的整体反照率反推单次散射反照率（Chiang 等人 2016 的拟合）
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0.0, 1.0);
+    let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+    1.0 - s * s
+}
+
+// 次表面散射：光线穿过光滑的电介质边界进入物体，在内部的均匀介质中随机游走，
+// 直到再次穿过边界离开。整段游走在一次 scatter 里完成，出射光线从离开的那一点发出
+struct SubsurfaceMaterial {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    sigma_t: Color, // 每个颜色通道的消光系数，即平均自由程的倒数
+    albedo: Color,  // 单次散射反照率 σs/σt
+    refraction_index: f64,
+}
+
+impl SubsurfaceMaterial {
+    fn new(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        mean_free_path: Color,
+        albedo: Color,
+        refraction_index: f64,
+    ) -> Self {
+        Self {
+            boundary,
+            sigma_t: Color::new(
+                1.0 / mean_free_path.x(),
+                1.0 / mean_free_path.y(),
+                1.0 / mean_free_path.z(),
+            ),
+            albedo: Color::new(
+                single_scattering_albedo(albedo.x()),
+                single_scattering_albedo(albedo.y()),
+                single_scattering_albedo(albedo.z()),
+            ),
+            refraction_index,
+        }
+    }
+
+    // 在边界上按菲涅尔反射率选择反射或折射，返回新的方向和是否发生了折射。
+    // 进出各有一次 1/η² 和 η² 的辐射度缩放，正好抵消，这里都不计
+    fn cross_boundary(&self, direction: Vec3, hit: &HitRecord) -> (Vec3, bool) {
+        let outward = if hit.front_face {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let r = fresnel_dielectric(dot(-direction, outward), self.refraction_index);
+        if random_double() < r {
+            (reflect(direction, hit.normal), false)
+        } else {
+            let ri = if hit.front_face {
+                1.0 / self.refraction_index
+            } else {
+                self.refraction_index
+            };
+            (unit_vector(refract(direction, hit.normal, ri)), true)
+        }
+    }
+
+    // 按当前路径权重的比例选一个颜色通道来采样自由程，
+    // 其余通道用所有通道的混合密度做权重（单样本 MIS）
+    fn pick_channel(throughput: Color) -> usize {
+        let total = throughput.x() + throughput.y() + throughput.z();
+        let u = random_double() * total;
+        if u < throughput.x() {
+            0
+        } else if u < throughput.x() + throughput.y() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.sigma_t.x() * distance).exp(),
+            (-self.sigma_t.y() * distance).exp(),
+            (-self.sigma_t.z() * distance).exp(),
+        )
+    }
+
+    // 各通道被选中的概率
+    fn channel_probabilities(throughput: Color) -> Color {
+        throughput / (throughput.x() + throughput.y() + throughput.z())
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut direction = unit_vector(r_in.direction());
+        let mut hit = rec.clone();
+        let mut events = 0;
+
+        loop {
+            let (next, refracted) = self.cross_boundary(direction, &hit);
+            // 折射后在物体内部当且仅当这次是从外面打进来的，反射则相反
+            if refracted != hit.front_face {
+                srec.attenuation = throughput;
+                srec.skip_pdf = true;
+                srec.skip_pdf_ray = Ray::new_time(hit.p, next, r_in.time());
+                return true;
+            }
+            direction = next;
+            let mut origin = hit.p;
+            // 从边界出发时要避开起点所在的表面；从介质内部的散射点出发时没有这个问题，
+            // 用很小的下限以免漏掉非常近的边界
+            let mut t_min = 0.001;
+
+            // 在介质里游走，直到再次碰到边界
+            loop {
+                events += 1;
+                if events > MAX_EVENTS || throughput.length_squared() <= 0.0 {
+                    return false;
+                }
+                let ray = Ray::new_time(origin, direction, r_in.time());
+                let mut exit = HitRecord::default();
+                // 边界不封闭时光线会逃出去，当作被吸收
+                if !self
+                    .boundary
+                    .hit(&ray, &Interval::new(t_min, INFINITY), &mut exit)
+                {
+                    return false;
+                }
+
+                let p = Self::channel_probabilities(throughput);
+                let channel = Self::pick_channel(throughput);
+                let distance = free_flight_distance(self.sigma_t[channel]);
+                if distance < exit.t {
+                    let tr = self.transmittance(distance);
+                    let pdf = dot(p, self.sigma_t * tr);
+                    if pdf <= 0.0 {
+                        return false;
+                    }
+                    throughput = throughput * self.albedo * self.sigma_t * tr / pdf;
+                    origin = ray.at(distance);
+                    direction = random_unit_vector();
+                    t_min = 1e-9;
+
+                    // 游走很长时用俄罗斯轮盘赌提前结束
+                    if events > 8 {
+                        let q = throughput.x().max(throughput.y()).max(throughput.z());
+                        if q < 1.0 {
+                            if random_double() >= q {
+                                return false;
+                            }
+                            throughput /= q;
+                        }
+                    }
+                } else {
+                    let tr = self.transmittance(exit.t);
+                    throughput = throughput * tr / dot(p, tr);
+                    hit = exit;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// 把封闭的边界物体变成半透明的次表面散射物体，用法和 ConstantMedium 类似。
+// 随机游走直接在 boundary 的坐标中进行，所以平移、旋转要加在 boundary 上，而不是这个物体外面
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Subsurface {
+    // mean_free_path 是每个颜色通道在介质中的平均自由程，albedo 是希望看到的整体颜色
+    pub fn new(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        mean_free_path: Color,
+        albedo: Color,
+        refraction_index: f64,
+    ) -> Self {
+        let material = SubsurfaceMaterial::new(
+            Arc::clone(&boundary),
+            mean_free_path,
+            albedo,
+            refraction_index,
+        );
+        Self {
+            boundary,
+            material: Arc::new(material),
+        }
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        if !self.boundary.hit(r, ray_t, rec) {
+            return false;
+        }
+        rec.mat = Some(Arc::clone(&self.material));
+        true
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::hittable::Sphere;
+    use crate::easy_task::material::Lambertian;
+    use crate::easy_task::vec3::Point3;
+
+    // 以原点为球心的单位球
+    fn material(mean_free_path: Color, albedo: Color, refraction_index: f64) -> SubsurfaceMaterial {
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        SubsurfaceMaterial::new(boundary, mean_free_path, albedo, refraction_index)
+    }
+
+    // 法线朝向入射的一侧；front_face 为 true 时外法线是 +y，否则是 -y
+    fn rec(front_face: bool) -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            front_face,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn albedo_fit_at_the_ends() {
+        assert!(single_scattering_albedo(0.0).abs() < 1e-4);
+        assert!((single_scattering_albedo(1.0) - 1.0).abs() < 1e-4);
+        // 范围外的输入截断到 [0, 1]
+        assert_eq!(
+            single_scattering_albedo(-1.0),
+            single_scattering_albedo(0.0)
+        );
+        assert_eq!(single_scattering_albedo(2.0), single_scattering_albedo(1.0));
+    }
+
+    #[test]
+    fn cross_boundary_reflects_or_enters() {
+        // 折射率为 1 时没有反射，方向不变
+        let matched = material(Color::new(1.0, 1.0, 1.0), Color::new(1.0, 1.0, 1.0), 1.0);
+        let d = unit_vector(Vec3::new(1.0, -1.0, 0.0));
+        for front_face in [true, false] {
+            let (next, refracted) = matched.cross_boundary(d, &rec(front_face));
+            assert!(refracted);
+            assert!((next - d).length() < 1e-12);
+        }
+
+        // 从内部以超过临界角的角度打到边界时全反射
+        let m = material(Color::new(1.0, 1.0, 1.0), Color::new(1.0, 1.0, 1.0), 1.5);
+        let grazing = unit_vector(Vec3::new(1.0, -0.2, 0.0));
+        for _ in 0..100 {
+            let (next, refracted) = m.cross_boundary(grazing, &rec(false));
+            assert!(!refracted);
+            assert!((next - Vec3::new(grazing.x(), -grazing.y(), 0.0)).length() < 1e-12);
+        }
+
+        // 从外面垂直入射时按 ((η - 1) / (η + 1))² = 0.04 的比例反射，其余折射进入
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let n = 100_000;
+        let mut reflected = 0;
+        for _ in 0..n {
+            let (next, refracted) = m.cross_boundary(down, &rec(true));
+            if refracted {
+                assert!((next - down).length() < 1e-12);
+            } else {
+                assert!((next + down).length() < 1e-12);
+                reflected += 1;
+            }
+        }
+        let fraction = reflected as f64 / n as f64;
+        assert!((fraction - 0.04).abs() < 0.003, "reflected {}", fraction);
+    }
+
+    // 没有吸收的封闭球：光线最终一定会离开，所以 scatter 不会失败，出射权重的平均值是 1
+    #[test]
+    fn closed_sphere_without_absorption_conserves_energy() {
+        let r_in = Ray::new(Point3::new(0.3, 0.2, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let white = Color::new(1.0, 1.0, 1.0);
+        for mean_free_path in [Color::new(0.2, 0.2, 0.2), Color::new(0.1, 0.2, 0.4)] {
+            let m = material(mean_free_path, white, 1.4);
+            let mut rec = HitRecord::default();
+            assert!(
+                m.boundary
+                    .hit(&r_in, &Interval::new(0.001, INFINITY), &mut rec)
+            );
+
+            let n = 20_000;
+            let mut sum = Color::default();
+            let mut failures = 0;
+            for _ in 0..n {
+                let mut srec = ScatterRecord::default();
+                if m.scatter(&r_in, &rec, &mut srec) {
+                    sum += srec.attenuation;
+                    // 出射点在球面上
+                    assert!((srec.skip_pdf_ray.origin().length() - 1.0).abs() < 1e-6);
+                } else {
+                    failures += 1;
+                }
+            }
+            let mean = sum / n as f64;
+            // 各通道相同时权重一直是 1，轮盘赌不会生效，只有超过 MAX_EVENTS 才会失败
+            if mean_free_path.x() == mean_free_path.z() {
+                assert_eq!(failures, 0);
+            }
+            for i in 0..3 {
+                assert!((mean[i] - 1.0).abs() < 0.05, "channel {}: {}", i, mean[i]);
+            }
+        }
+    }
+}
@@ -85,12 +85,15 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
-    #[allow(dead_code)]
     pub fn new(filename: &str) -> Self {
         Self {
             image: RtwImage::new(filename),
         }
     }
+
+    pub fn try_new(filename: &str) -> Option<Self> {
+        RtwImage::try_new(filename).map(|image| Self { image })
+    }
 }
 
 impl Texture for ImageTexture {
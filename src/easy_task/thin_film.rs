@@ -0,0 +1,236 @@
+use crate::easy_task::color::Color;
+use crate::easy_task::hittable::HitRecord;
+use crate::easy_task::spectral::{SampledWavelengths, reflectance_to_rgb};
+use crate::easy_task::texture::{SolidColor, Texture};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// 表面上的一层透明薄膜：膜上下两个界面反射的光发生干涉，相位差随膜厚、角度和波长变化，
+// 一些波长相互加强、另一些相互抵消，于是出现肥皂泡和油膜上的彩虹色
+pub struct ThinFilm {
+    thickness: Arc<dyn Texture + Send + Sync>, // 取第一个分量，在最小和最大厚度之间插值
+    min_thickness: f64,                        // nm
+    max_thickness: f64,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        Self::new_textured(
+            Arc::new(SolidColor::new(Color::default())),
+            thickness,
+            thickness,
+            ior,
+        )
+    }
+
+    pub fn new_textured(
+        thickness: Arc<dyn Texture + Send + Sync>,
+        min_thickness: f64,
+        max_thickness: f64,
+        ior: f64,
+    ) -> Self {
+        Self {
+            thickness,
+            min_thickness,
+            max_thickness,
+            ior,
+        }
+    }
+
+    // 从空气以夹角余弦 cos_i 照到膜上、膜下是折射率 substrate_ior 的透明基底时的反射率。
+    // 给出 wavelengths 时三个分量是这几个波长上的值，否则是 RGB 反射率
+    pub fn reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_i: f64,
+        substrate_ior: f64,
+        wavelengths: Option<SampledWavelengths>,
+    ) -> Color {
+        let n2 = Complex::new(substrate_ior, 0.0);
+        self.evaluate(rec, cos_i, wavelengths, |_| n2)
+    }
+
+    // 膜下是复折射率为 η + ik 的导体，η 和 k 是约 650、550、450nm 处的值，之间线性插值
+    pub fn reflectance_conductor(
+        &self,
+        rec: &HitRecord,
+        cos_i: f64,
+        eta: Color,
+        k: Color,
+        wavelengths: Option<SampledWavelengths>,
+    ) -> Color {
+        self.evaluate(rec, cos_i, wavelengths, |lambda| {
+            Complex::new(channel_value(eta, lambda), channel_value(k, lambda))
+        })
+    }
+
+    fn evaluate(
+        &self,
+        rec: &HitRecord,
+        cos_i: f64,
+        wavelengths: Option<SampledWavelengths>,
+        substrate: impl Fn(f64) -> Complex,
+    ) -> Color {
+        let t = self
+            .thickness
+            .value(rec.u, rec.v, rec.p)
+            .x()
+            .clamp(0.0, 1.0);
+        let d = self.min_thickness + (self.max_thickness - self.min_thickness) * t;
+        let cos_i = cos_i.abs().min(1.0);
+        let reflectance = |lambda| airy_reflectance(cos_i, d, lambda, self.ior, substrate(lambda));
+        match wavelengths {
+            // 光谱模式直接在路径的波长上求值，干涉条纹不会先被压成 RGB 再展开
+            Some(w) => Color::new(
+                reflectance(w.lambda[0]),
+                reflectance(w.lambda[1]),
+                reflectance(w.lambda[2]),
+            ),
+            None => reflectance_to_rgb(reflectance),
+        }
+    }
+}
+
+fn channel_value(c: Color, lambda: f64) -> f64 {
+    if lambda < 550.0 {
+        let t = ((lambda - 450.0) / 100.0).max(0.0);
+        c.z() + (c.y() - c.z()) * t
+    } else {
+        let t = ((lambda - 550.0) / 100.0).min(1.0);
+        c.y() + (c.x() - c.y()) * t
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm2(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // 主值平方根，实部非负
+    fn sqrt(self) -> Self {
+        let r = self.norm2().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    fn exp_i(phase: f64) -> Self {
+        Self::new(phase.cos(), phase.sin())
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Self;
+    fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Self;
+    fn div(self, o: Self) -> Self {
+        let n = o.norm2();
+        Self::new(
+            (self.re * o.re + self.im * o.im) / n,
+            (self.im * o.re - self.re * o.im) / n,
+        )
+    }
+}
+
+// 空气 | 膜（折射率 n1，厚度 d）| 基底（复折射率 n2）三层结构的反射率，
+// 包含膜内所有次数的反射，对 s、p 两种偏振取平均。d 和 lambda 的单位都是 nm
+fn airy_reflectance(cos0: f64, d: f64, lambda: f64, n1: f64, n2: Complex) -> f64 {
+    let one = Complex::new(1.0, 0.0);
+    let sin2_0 = Complex::new(1.0 - cos0 * cos0, 0.0);
+    let n0 = one;
+    let n1 = Complex::new(n1, 0.0);
+    let cos0 = Complex::new(cos0, 0.0);
+    // 斯涅尔定律 n0 sinθ0 = n sinθ，全反射或导体中的角度是复数
+    let cos_in = |n: Complex| (one - sin2_0 / (n * n)).sqrt();
+    let cos1 = cos_in(n1);
+    let cos2 = cos_in(n2);
+
+    // 在膜里走一个来回的相位差，膜内全反射时是衰减而不是相移
+    let k = 4.0 * PI * d / lambda;
+    let delta = n1 * cos1 * Complex::new(k, 0.0);
+    let propagation = Complex::exp_i(delta.re) * Complex::new((-delta.im).exp(), 0.0);
+
+    let airy = |r01: Complex, r12: Complex| {
+        let r12 = r12 * propagation;
+        ((r01 + r12).norm2() / (one + r01 * r12).norm2()).clamp(0.0, 1.0)
+    };
+    let s = airy(
+        (n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1),
+        (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+    );
+    let p = airy(
+        (n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1),
+        (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+    );
+    (s + p) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::spectral;
+
+    #[test]
+    fn spectral_mode_evaluates_at_path_wavelengths() {
+        let film = ThinFilm::new(300.0, 1.33);
+        let rec = HitRecord::default();
+        let n2 = Complex::new(1.0, 0.0);
+        for cos in [1.0, 0.7, 0.2] {
+            let w = SampledWavelengths::sample();
+            let r = film.reflectance(&rec, cos, 1.0, Some(w));
+            for (i, &lambda) in w.lambda.iter().enumerate() {
+                assert_eq!(r[i], airy_reflectance(cos, 300.0, lambda, 1.33, n2));
+            }
+            let rgb = film.reflectance(&rec, cos, 1.0, None);
+            let expected = spectral::reflectance_to_rgb(|lambda| {
+                airy_reflectance(cos, 300.0, lambda, 1.33, n2)
+            });
+            assert!((rgb - expected).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_film_cancels_reflection() {
+        // 膜的折射率是基底的平方根、光学厚度为四分之一波长时，正入射的两束反射光正好抵消
+        let (n1, n2, lambda) = (1.25, 1.5625, 550.0);
+        let d = lambda / (4.0 * n1);
+        let r = airy_reflectance(1.0, d, lambda, n1, Complex::new(n2, 0.0));
+        assert!(r < 1e-12);
+        let bare = ((n2 - 1.0) / (n2 + 1.0)).powi(2);
+        let r = airy_reflectance(1.0, 2.0 * d, lambda, n1, Complex::new(n2, 0.0));
+        assert!((r - bare).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::easy_task::color::{Color, linear_to_srgb};
+use crate::easy_task::framebuffer::Framebuffer;
+use crate::easy_task::interval::Interval;
+
+// 把高动态范围的辐射度压缩到 [0, 1] 的算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapper {
+    #[default]
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    Hable,
+    Aces,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ExtendedReinhard,
+        ToneMapper::Hable,
+        ToneMapper::Aces,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapper::Clamp => "clamp",
+            ToneMapper::Reinhard => "reinhard",
+            ToneMapper::ExtendedReinhard => "reinhard-extended",
+            ToneMapper::Hable => "hable",
+            ToneMapper::Aces => "aces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.name() == name)
+    }
+}
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// Uncharted 2 的分段曲线，参数取自 John Hable 的原文
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+// Krzysztof Narkowicz 对 ACES 参考变换的有理函数拟合
+fn aces_fitted(x: f64) -> f64 {
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+// 输出低动态范围图像前的后处理：曝光 → 色调映射 → sRGB 编码 → 量化
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayTransform {
+    pub exposure: f64, // 以档（stop）为单位，每加 1 亮度翻倍
+    pub tone_mapper: ToneMapper,
+    // 扩展 Reinhard 中映射为纯白的（曝光后的）亮度，None 表示取画面中的最大亮度
+    pub white_point: Option<f64>,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapper: ToneMapper::Clamp,
+            white_point: None,
+        }
+    }
+}
+
+impl DisplayTransform {
+    // 对单个曝光后的线性颜色做色调映射，结果仍是线性值
+    fn map(&self, c: Color, white: f64) -> Color {
+        match self.tone_mapper {
+            ToneMapper::Clamp => c,
+            ToneMapper::Reinhard | ToneMapper::ExtendedReinhard => {
+                // 按亮度缩放，保持色相不变
+                let l = luminance(c);
+                if l <= 0.0 {
+                    return Color::default();
+                }
+                let mapped = if self.tone_mapper == ToneMapper::Reinhard {
+                    l / (1.0 + l)
+                } else {
+                    l * (1.0 + l / (white * white)) / (1.0 + l)
+                };
+                c * (mapped / l)
+            }
+            ToneMapper::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                let scale = 1.0 / hable_partial(WHITE);
+                Color::new(
+                    hable_partial(c.x() * EXPOSURE_BIAS) * scale,
+                    hable_partial(c.y() * EXPOSURE_BIAS) * scale,
+                    hable_partial(c.z() * EXPOSURE_BIAS) * scale,
+                )
+            }
+            ToneMapper::Aces => {
+                Color::new(aces_fitted(c.x()), aces_fitted(c.y()), aces_fitted(c.z()))
+            }
+        }
+    }
+
+    // 把整张帧缓冲转换成按行优先排列的 8 位 sRGB 像素
+    pub fn apply(&self, framebuffer: &Framebuffer) -> Vec<[u8; 3]> {
+        let scale = 2f64.powf(self.exposure);
+        let pixels: Vec<Color> = (0..framebuffer.height())
+            .flat_map(|j| (0..framebuffer.width()).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                // NaN 和负值都当作 0
+                let c = framebuffer.get(i, j);
+                Color::new(
+                    c.x().max(0.0) * scale,
+                    c.y().max(0.0) * scale,
+                    c.z().max(0.0) * scale,
+                )
+            })
+            .collect();
+
+        let white = self.white_point.unwrap_or_else(|| {
+            pixels
+                .iter()
+                .map(|&c| luminance(c))
+                .filter(|l| l.is_finite())
+                .fold(0.0, f64::max)
+        });
+        let white = if white > 0.0 { white } else { 1.0 };
+
+        let intensity = Interval::new(0.000, 0.999);
+        pixels
+            .into_iter()
+            .map(|c| {
+                let c = self.map(c, white);
+                [c.x(), c.y(), c.z()].map(|v| (256.0 * intensity.clamp(linear_to_srgb(v))) as u8)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,456 @@
+use crate::easy_task::aabb::Aabb;
+use crate::easy_task::bvh_node::{BvhNode, BvhSplit};
+use crate::easy_task::hittable::{HitRecord, Hittable};
+use crate::easy_task::interval::Interval;
+use crate::easy_task::material::Material;
+use crate::easy_task::ray::Ray;
+use crate::easy_task::rtweekend::random_double;
+use crate::easy_task::vec3::{Point3, Vec3, cross, dot, unit_vector};
+use std::sync::Arc;
+
+// Möller–Trumbore 求交，返回 (t, b1, b2)，其中 b1、b2 是 v1、v2 的重心坐标
+fn intersect(v0: Point3, e1: Vec3, e2: Vec3, r: &Ray, ray_t: &Interval) -> Option<(f64, f64, f64)> {
+    let pvec = cross(r.direction(), e2);
+    let det = dot(e1, pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - v0;
+    let b1 = dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(tvec, e1);
+    let b2 = dot(r.direction(), qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(e2, qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+// 在三角形上均匀采样一个点
+fn sample_point(v0: Point3, e1: Vec3, e2: Vec3) -> Point3 {
+    let su = random_double().sqrt();
+    let b1 = 1.0 - su;
+    let b2 = random_double() * su;
+    v0 + b1 * e1 + b2 * e2
+}
+
+fn triangle_bbox(a: Point3, b: Point3, c: Point3) -> Aabb {
+    Aabb::new_aabb(&Aabb::new_point(&a, &b), &Aabb::new_point(&a, &c)).pad()
+}
+
+#[derive(Clone)]
+pub struct Triangle {
+    v0: Point3,
+    e1: Vec3,
+    e2: Vec3,
+    normal: Vec3,
+    area: f64,
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    #[allow(dead_code)]
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        Self::try_new(v0, v1, v2, mat).expect("degenerate triangle (zero area)")
+    }
+
+    // 三个顶点共线时面积为 0，没有法线，返回 None
+    pub fn try_new(
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        mat: Arc<dyn Material + Send + Sync>,
+    ) -> Option<Self> {
+        let e1 = v1 - v0;
+        let e2 = v2 - v0;
+        let n = cross(e1, e2);
+        let area = 0.5 * n.length();
+        if area <= 0.0 || !area.is_finite() {
+            return None;
+        }
+        Some(Self {
+            v0,
+            e1,
+            e2,
+            normal: n / (2.0 * area),
+            area,
+            mat,
+            bbox: triangle_bbox(v0, v1, v2),
+        })
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let Some((t, b1, b2)) = intersect(self.v0, self.e1, self.e2, r, ray_t) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.u = b1;
+        rec.v = b2;
+        rec.mat = Some(Arc::clone(&self.mat));
+        rec.set_face_normal(r, self.normal);
+
+        true
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(origin, direction),
+            &Interval::new(0.0001, f64::INFINITY),
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(direction, self.normal) / direction.length()).abs();
+        if cosine * self.area <= 0.0 {
+            return 0.0;
+        }
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        sample_point(self.v0, self.e1, self.e2) - origin
+    }
+}
+
+// 网格的一个面，分别索引顶点、法线和纹理坐标缓冲区
+#[derive(Debug, Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+impl MeshFace {
+    #[allow(dead_code)]
+    pub fn new(vertices: [usize; 3]) -> Self {
+        Self {
+            vertices,
+            normals: None,
+            uvs: None,
+        }
+    }
+}
+
+fn face_area(positions: &[Point3], face: &MeshFace) -> f64 {
+    let [a, b, c] = face.vertices.map(|i| positions[i]);
+    0.5 * cross(b - a, c - a).length()
+}
+
+// 所有面共享的缓冲区
+struct MeshData {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    mat: Arc<dyn Material + Send + Sync>,
+    total_area: f64,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+    bbox: Aabb,
+    smooth: bool,
+}
+
+impl MeshTriangle {
+    fn corners(&self) -> (Point3, Vec3, Vec3) {
+        let [i0, i1, i2] = self.mesh.faces[self.face].vertices;
+        let v0 = self.mesh.positions[i0];
+        (
+            v0,
+            self.mesh.positions[i1] - v0,
+            self.mesh.positions[i2] - v0,
+        )
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        let (v0, e1, e2) = self.corners();
+        let Some((t, b1, b2)) = intersect(v0, e1, e2, r, ray_t) else {
+            return false;
+        };
+        let b0 = 1.0 - b1 - b2;
+        let face = &self.mesh.faces[self.face];
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.mat = Some(Arc::clone(&self.mesh.mat));
+
+        (rec.u, rec.v) = match face.uvs {
+            Some([t0, t1, t2]) => {
+                let (uv0, uv1, uv2) = (self.mesh.uvs[t0], self.mesh.uvs[t1], self.mesh.uvs[t2]);
+                (
+                    b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                    b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+                )
+            }
+            None => (b1, b2),
+        };
+
+        // 正反面由几何法线决定，着色法线翻转到同一侧
+        let geometric = unit_vector(cross(e1, e2));
+        rec.set_face_normal(r, geometric);
+        if let (true, Some([n0, n1, n2])) = (self.smooth, face.normals) {
+            let shading = unit_vector(
+                b0 * self.mesh.normals[n0]
+                    + b1 * self.mesh.normals[n1]
+                    + b2 * self.mesh.normals[n2],
+            );
+            rec.normal = if dot(shading, rec.normal) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+        }
+
+        true
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        &self.bbox
+    }
+
+    // 整个网格按面积均匀采样时这个面贡献的密度，用几何法线而不是插值的着色法线
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let (v0, e1, e2) = self.corners();
+        let r = Ray::new(origin, direction);
+        let Some((t, _, _)) = intersect(v0, e1, e2, &r, &Interval::new(0.0001, f64::INFINITY))
+        else {
+            return 0.0;
+        };
+        let distance_squared = t * t * direction.length_squared();
+        let cosine = (dot(direction, unit_vector(cross(e1, e2))) / direction.length()).abs();
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        distance_squared / (cosine * self.mesh.total_area)
+    }
+}
+
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: Arc<dyn Hittable + Send + Sync>,
+    // 按面积累积的分布，用于把网格当作面光源采样
+    area_cdf: Vec<f64>,
+}
+
+impl TriangleMesh {
+    #[allow(dead_code)]
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        mat: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        Self::try_new(positions, normals, uvs, faces, mat)
+            .expect("TriangleMesh needs at least one face with a non-zero area")
+    }
+
+    // 面积为 0 的面被跳过，一个有效的面都没有时返回 None
+    pub fn try_new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        mat: Arc<dyn Material + Send + Sync>,
+    ) -> Option<Self> {
+        Self::try_new_with(positions, normals, uvs, faces, mat, BvhSplit::default())
+    }
+
+    // split 是网格内部 BVH 的划分策略
+    pub fn try_new_with(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        mat: Arc<dyn Material + Send + Sync>,
+        split: BvhSplit,
+    ) -> Option<Self> {
+        let faces: Vec<MeshFace> = faces
+            .into_iter()
+            .filter(|f| face_area(&positions, f) > 0.0)
+            .collect();
+        if faces.is_empty() {
+            return None;
+        }
+
+        let mut area_cdf = Vec::with_capacity(faces.len());
+        let mut total_area = 0.0;
+        for f in &faces {
+            total_area += face_area(&positions, f);
+            area_cdf.push(total_area);
+        }
+
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            faces,
+            mat,
+            total_area,
+        });
+
+        let smooth = mesh.faces.iter().any(|f| f.normals.is_some());
+        let mut triangles: Vec<Arc<dyn Hittable + Send + Sync>> =
+            Vec::with_capacity(mesh.faces.len());
+
+        for (face, f) in mesh.faces.iter().enumerate() {
+            let [i0, i1, i2] = f.vertices;
+            let (a, b, c) = (mesh.positions[i0], mesh.positions[i1], mesh.positions[i2]);
+            triangles.push(Arc::new(MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+                bbox: triangle_bbox(a, b, c),
+                smooth,
+            }));
+        }
+
+        let len = triangles.len() as i32;
+        let bvh: Arc<dyn Hittable + Send + Sync> =
+            Arc::new(BvhNode::new_with(&mut triangles, 0, len, split));
+
+        Some(Self {
+            mesh,
+            bvh,
+            area_cdf,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn face_count(&self) -> usize {
+        self.mesh.faces.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        self.bvh.bounding_box()
+    }
+
+    // 面积均匀采样时面积测度下的密度处处为 1/A，BVH 累加光线穿过的每一个面
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.bvh.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let target = random_double() * self.mesh.total_area;
+        let face = self
+            .area_cdf
+            .partition_point(|&a| a < target)
+            .min(self.area_cdf.len() - 1);
+
+        let [i0, i1, i2] = self.mesh.faces[face].vertices;
+        let v0 = self.mesh.positions[i0];
+        let p = sample_point(
+            v0,
+            self.mesh.positions[i1] - v0,
+            self.mesh.positions[i2] - v0,
+        );
+        p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_task::color::Color;
+    use crate::easy_task::material::Lambertian;
+
+    fn material() -> Arc<dyn Material + Send + Sync> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn degenerate_triangle_is_rejected() {
+        let a = Point3::new(0.0, 0.0, 0.0);
+        let b = Point3::new(1.0, 1.0, 1.0);
+        let c = Point3::new(2.0, 2.0, 2.0);
+        assert!(Triangle::try_new(a, b, c, material()).is_none());
+        assert!(Triangle::try_new(a, a, c, material()).is_none());
+    }
+
+    #[test]
+    fn mesh_skips_degenerate_faces() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+        ];
+        let faces = vec![MeshFace::new([0, 1, 3]), MeshFace::new([0, 1, 2])];
+        let mesh =
+            TriangleMesh::try_new(positions.clone(), vec![], vec![], faces, material()).unwrap();
+        assert_eq!(mesh.face_count(), 1);
+
+        // 从正上方看向三角形：dA 投影到立体角，p = d² / (cosθ · A) = 1 / 0.5
+        let origin = Point3::new(0.25, 0.25, 1.0);
+        let pdf = mesh.pdf_value(origin, Vec3::new(0.0, 0.0, -1.0));
+        assert!((pdf - 2.0).abs() < 1e-9, "{}", pdf);
+        // 沿着三角形所在平面的方向与它不相交
+        assert_eq!(mesh.pdf_value(origin, Vec3::new(1.0, 0.0, 0.0)), 0.0);
+
+        let only_degenerate = vec![MeshFace::new([0, 1, 3])];
+        assert!(
+            TriangleMesh::try_new(positions, vec![], vec![], only_degenerate, material()).is_none()
+        );
+    }
+
+    #[test]
+    fn smooth_mesh_pdf_uses_geometric_normals() {
+        // 两个面积都是 0.5 的平行面，顶点法线故意倾斜
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(0.0, 1.0, -1.0),
+        ];
+        let normals = vec![unit_vector(Vec3::new(1.0, 0.0, 1.0))];
+        let face = |v: [usize; 3]| MeshFace {
+            vertices: v,
+            normals: Some([0, 0, 0]),
+            uvs: None,
+        };
+        let faces = vec![face([0, 1, 2]), face([3, 4, 5])];
+        let mesh = TriangleMesh::try_new(positions, normals, vec![], faces, material()).unwrap();
+
+        // 总面积为 1，光线依次穿过两个面：p = Σ d² / (cosθ · A) = 1 + 4
+        let origin = Point3::new(0.25, 0.25, 1.0);
+        let pdf = mesh.pdf_value(origin, Vec3::new(0.0, 0.0, -1.0));
+        assert!((pdf - 5.0).abs() < 1e-9, "{}", pdf);
+        let pdf = mesh.pdf_value(origin, Vec3::new(0.0, 0.0, -3.0));
+        assert!((pdf - 5.0).abs() < 1e-9, "{}", pdf);
+    }
+}
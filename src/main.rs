@@ -1,18 +1,25 @@
+mod bench;
+mod cli;
 mod easy_task;
-use crate::easy_task::bvh_node::BvhNode;
+use crate::bench::bench_bvh;
+use crate::cli::{Command, parse_args, print_help};
+use crate::easy_task::bvh_node::{BvhConfig, build_bvh};
 use crate::easy_task::camera::Camera;
 use crate::easy_task::color::Color;
 use crate::easy_task::constant_medium::ConstantMedium;
+use crate::easy_task::environment::EnvironmentLight;
 use crate::easy_task::hittable::{Hittable, RotateY, Sphere, Translate};
 use crate::easy_task::hittable_list::HittableList;
 use crate::easy_task::material::{Dielectric, DiffuseLight, Lambertian, Material};
 use crate::easy_task::quad::{Quad, box_};
-use crate::easy_task::rtweekend::random_double_range;
+use crate::easy_task::rtw_image::HdrImage;
+use crate::easy_task::rtweekend::{random_double_range, seed_thread_rng};
+use crate::easy_task::scene::{Scene, load_scene};
+use crate::easy_task::sky::PhysicalSky;
 use crate::easy_task::texture::{ImageTexture, Texture};
 use crate::easy_task::vec3::{Point3, Vec3, random_range};
 use std::sync::Arc;
-#[allow(dead_code)]
-fn cornell_box() {
+fn cornell_box(bvh: BvhConfig) -> Scene {
     let mut world = HittableList::default();
 
     let red: Arc<dyn Material + Sync + Send> =
@@ -105,20 +112,118 @@ fn cornell_box() {
 
     cam.defocus_angle = 0.0;
 
-    cam.render(Arc::new(world), Arc::new(lights));
+    Scene {
+        camera: cam,
+        world: build_bvh(&mut world, bvh),
+        lights: Some(Arc::new(lights)),
+    }
 }
 
+type SceneFn = fn(BvhConfig) -> Scene;
+
+const SCENES: [(&str, &str, SceneFn); 3] = [
+    (
+        "cornell_box",
+        "Cornell box with a rotated box and a glass sphere",
+        cornell_box,
+    ),
+    ("earth", "A textured globe in front of a blue sky", earth),
+    (
+        "final_scene",
+        "Book 2 final scene with textured planets and fog (default)",
+        final_scene,
+    ),
+];
+
 fn main() {
-    final_scene(800, 3000, 40)
+    let (options, bench) = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => (options, false),
+        Ok(Command::Bench(options)) => (options, true),
+        Ok(Command::Help) => {
+            print_help(&SCENES.map(|(name, description, _)| (name, description)));
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Run with --help to see the available options.");
+            std::process::exit(2);
+        }
+    };
+
+    if bench {
+        match options.scene.as_deref() {
+            Some(name) => {
+                let build = scene_builder(name);
+                bench_bvh(&[(name, &*build)], &options);
+            }
+            None => {
+                let builders: Vec<(&str, &dyn Fn(BvhConfig) -> Scene)> = SCENES
+                    .iter()
+                    .map(|(name, _, build)| (*name, build as &dyn Fn(BvhConfig) -> Scene))
+                    .collect();
+                bench_bvh(&builders, &options);
+            }
+        }
+        return;
+    }
+
+    // 场景构建本身也会用到随机数（比如 final_scene 中盒子的高度）
+    if let Some(seed) = options.seed {
+        seed_thread_rng(seed);
+    }
+
+    let mut scene =
+        scene_builder(options.scene.as_deref().unwrap_or("final_scene"))(options.bvh_config());
+    options.apply(&mut scene.camera);
+    if let Some(file) = &options.environment {
+        let Some(image) = HdrImage::try_new(file) else {
+            eprintln!("error: cannot load environment map \"{}\"", file);
+            std::process::exit(1);
+        };
+        scene.set_environment(Arc::new(EnvironmentLight::new(
+            image,
+            options.env_intensity.unwrap_or(1.0),
+            options.env_rotation.unwrap_or(0.0),
+        )));
+    }
+    if options.sky {
+        scene.set_environment(Arc::new(PhysicalSky::new(
+            options.sun_elevation.unwrap_or(45.0),
+            options.sun_azimuth.unwrap_or(0.0),
+            options.turbidity.unwrap_or(3.0),
+        )));
+    }
+    scene.render();
+}
+
+// 按名字查找内置场景，找不到时当作场景文件路径
+fn scene_builder(name: &str) -> Box<dyn Fn(BvhConfig) -> Scene + '_> {
+    if let Some((_, _, build)) = SCENES.iter().find(|(n, _, _)| *n == name) {
+        return Box::new(build);
+    }
+    if !name.ends_with(".json") && !std::path::Path::new(name).is_file() {
+        eprintln!("error: unknown scene \"{}\"", name);
+        eprintln!("Run with --help to list the built-in scenes.");
+        std::process::exit(2);
+    }
+    Box::new(move |bvh| match load_scene(name, bvh) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    })
 }
-#[allow(dead_code)]
-fn earth() {
+
+fn earth(bvh: BvhConfig) -> Scene {
     let earth_texture: Arc<dyn Texture + Sync + Send> = Arc::new(ImageTexture::new("gyn2.jpg"));
     let earth_surface: Arc<dyn Material + Sync + Send> =
         Arc::new(Lambertian::new_texture(Arc::clone(&earth_texture)));
     let mut globe: Arc<dyn Hittable + Sync + Send> =
         Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, earth_surface));
     globe = Arc::new(RotateY::new(globe, -90.0));
+    let mut world = HittableList::default();
+    world.add(globe);
     let light: Arc<dyn Material + Sync + Send> =
         Arc::new(DiffuseLight::new_color(Color::new(15.0, 15.0, 15.0)));
     let mut lights = HittableList::default();
@@ -149,9 +254,14 @@ fn earth() {
 
     cam.defocus_angle = 0.0;
 
-    cam.render(globe, Arc::new(lights));
+    Scene {
+        camera: cam,
+        world: build_bvh(&mut world, bvh),
+        lights: Some(Arc::new(lights)),
+    }
 }
-fn final_scene(image_width: usize, samples_per_pixel: usize, max_depth: usize) {
+
+fn final_scene(bvh: BvhConfig) -> Scene {
     let mut boxes1 = HittableList::default();
     let ground: Arc<dyn Material + Send + Sync> =
         Arc::new(Lambertian::new(Color::new(0.9, 0.9, 0.9)));
@@ -177,7 +287,7 @@ fn final_scene(image_width: usize, samples_per_pixel: usize, max_depth: usize) {
 
     let mut world = HittableList::default();
 
-    world.add(Arc::new(BvhNode::new_list(&mut boxes1)));
+    world.add(build_bvh(&mut boxes1, bvh));
 
     let light: Arc<dyn Material + Send + Sync> =
         Arc::new(DiffuseLight::new_color(Color::new(7.0, 7.0, 7.0)));
@@ -286,16 +396,16 @@ fn final_scene(image_width: usize, samples_per_pixel: usize, max_depth: usize) {
     });
 
     world.add(Arc::new(Translate::new(
-        Arc::new(RotateY::new(Arc::new(BvhNode::new_list(&mut boxes2)), 15.0)),
+        Arc::new(RotateY::new(build_bvh(&mut boxes2, bvh), 15.0)),
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
     let mut cam = Camera::default();
 
     cam.aspect_ratio = 1.0;
-    cam.image_width = image_width as i32;
-    cam.samples_per_pixel = samples_per_pixel as i32;
-    cam.max_depth = max_depth as i32;
+    cam.image_width = 800;
+    cam.samples_per_pixel = 3000;
+    cam.max_depth = 40;
     cam.background = Color::default();
 
     cam.vfov = 40.0;
@@ -305,7 +415,9 @@ fn final_scene(image_width: usize, samples_per_pixel: usize, max_depth: usize) {
 
     cam.defocus_angle = 0.0;
 
-    let world: Arc<dyn Hittable + Send + Sync> = Arc::new(world);
-
-    cam.render(world, Arc::new(lights));
+    Scene {
+        camera: cam,
+        world: Arc::new(world),
+        lights: Some(Arc::new(lights)),
+    }
 }